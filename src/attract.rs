@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use strum::EnumCount;
+
+use crate::parts::GamePart;
+
+pub const DEFAULT_ATTRACT_SECONDS: u64 = 60;
+const FIRST_ATTRACT_PART: GamePart = GamePart::Two;
+
+/// Kiosk playback: every part runs for a fixed time, or until its channels
+/// halt, before the next one is forced in. Wraps around after the last part.
+pub struct AttractMode {
+    duration: Duration,
+    part: GamePart,
+    started: Instant,
+}
+
+impl AttractMode {
+    pub fn new(duration: Duration, part: GamePart) -> Self {
+        Self {
+            duration,
+            part,
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns the part to switch to, if the current one has run its course.
+    pub fn next_part(
+        &mut self,
+        current: GamePart,
+        halted: bool,
+        is_available: impl Fn(GamePart) -> bool,
+    ) -> Option<GamePart> {
+        if current != self.part {
+            self.part = current;
+            self.started = Instant::now();
+        }
+        if !halted && self.started.elapsed() < self.duration {
+            return None;
+        }
+
+        let next = |part: GamePart| part.successor().unwrap_or(FIRST_ATTRACT_PART);
+        let mut candidate = next(current);
+        for _ in 0..GamePart::COUNT {
+            if is_available(candidate) {
+                break;
+            }
+            candidate = next(candidate);
+        }
+        self.started = Instant::now();
+        Some(candidate)
+    }
+}
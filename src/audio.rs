@@ -0,0 +1,829 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use byteorder::{BigEndian, ByteOrder};
+use lewton::{VorbisError, inside_ogg::OggStreamReader};
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::{
+    bank::find_file,
+    resource::{ResourceError, ResourceRegistry},
+};
+
+pub const NUM_VOICES: usize = 4;
+/// Rate used while no output stream is open.
+const DEFAULT_OUTPUT_RATE: u32 = 44_100;
+const MAX_VOLUME: u8 = 0x3F;
+const SAMPLE_HEADER_LEN: usize = 8;
+const NUM_INSTRUMENTS: usize = 15;
+const MODULE_ORDER_COUNT_OFFSET: usize = 0x3E;
+const MODULE_ORDERS_OFFSET: usize = 0x40;
+const MODULE_ORDERS_LEN: usize = 0x80;
+const MODULE_PATTERNS_OFFSET: usize = 0xC0;
+const PATTERN_LEN: usize = 1024;
+const ROW_LEN: usize = 4 * NUM_VOICES;
+/// Amiga PAL clock, used to turn the pattern periods into rates.
+const PAULA_CLOCK: u32 = 7_159_092;
+const MIN_PERIOD: u16 = 0x37;
+const NOTE_STOP: u16 = 0xFFFE;
+const NOTE_MARK: u16 = 0xFFFD;
+
+#[derive(Error, Debug)]
+pub enum AudioError {
+    #[error("Resource error")]
+    ResourceError(#[source] ResourceError),
+    #[error("Sound resource {index:#04X} is {len} bytes, shorter than its header")]
+    TruncatedSample { index: usize, len: usize },
+    #[error("Music resource {index:#04X} is {len} bytes, shorter than its header")]
+    TruncatedModule { index: usize, len: usize },
+    #[error("Error opening music track {0}")]
+    TrackOpen(PathBuf, #[source] io::Error),
+    #[error("Error decoding music track {0}")]
+    TrackDecode(PathBuf, #[source] VorbisError),
+    #[error("Music track {0} holds no audio")]
+    EmptyTrack(PathBuf),
+}
+
+impl From<ResourceError> for AudioError {
+    fn from(value: ResourceError) -> Self {
+        AudioError::ResourceError(value)
+    }
+}
+
+/// 8-bit signed PCM decoded from a sound resource: a big-endian length and
+/// loop length, both in words, four unused bytes, then the data. The loop,
+/// when there is one, follows the first `length` bytes.
+#[derive(Clone)]
+pub struct Sample {
+    data: Arc<[i8]>,
+    loop_start: usize,
+}
+
+impl Sample {
+    pub fn load(resource: &mut ResourceRegistry, index: usize) -> Result<Self, AudioError> {
+        let data = resource.load_entry(index)?;
+        Self::decode(&data).ok_or(AudioError::TruncatedSample {
+            index,
+            len: data.len(),
+        })
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let header = data.get(..SAMPLE_HEADER_LEN)?;
+        let len = BigEndian::read_u16(&header[0..]) as usize * 2;
+        let loop_len = BigEndian::read_u16(&header[2..]) as usize * 2;
+        let end = (SAMPLE_HEADER_LEN + len + loop_len).min(data.len());
+        let pcm: Arc<[i8]> = data[SAMPLE_HEADER_LEN..end]
+            .iter()
+            .map(|&b| b as i8)
+            .collect();
+        let loop_start = if loop_len == 0 { pcm.len() } else { len };
+        Some(Self {
+            loop_start: loop_start.min(pcm.len()),
+            data: pcm,
+        })
+    }
+
+    fn has_loop(&self) -> bool {
+        self.loop_start < self.data.len()
+    }
+}
+
+#[derive(Clone)]
+struct Instrument {
+    sample: Sample,
+    volume: u8,
+}
+
+/// A music resource with its instruments already decoded.
+#[derive(Clone)]
+pub struct Module {
+    delay: u16,
+    instruments: Vec<Option<Instrument>>,
+    order_count: u8,
+    orders: Arc<[u8]>,
+    patterns: Arc<[u8]>,
+}
+
+impl Module {
+    /// Loads the module at `index` and every sound resource it refers to.
+    pub fn load(resource: &mut ResourceRegistry, index: usize) -> Result<Self, AudioError> {
+        let data = resource.load_entry(index)?;
+        if data.len() < MODULE_PATTERNS_OFFSET {
+            return Err(AudioError::TruncatedModule {
+                index,
+                len: data.len(),
+            });
+        }
+
+        let mut instruments = Vec::with_capacity(NUM_INSTRUMENTS);
+        for header in data[2..].chunks_exact(4).take(NUM_INSTRUMENTS) {
+            let sample_index = BigEndian::read_u16(header) as usize;
+            instruments.push(match sample_index {
+                0 => None,
+                _ => Some(Instrument {
+                    sample: Sample::load(resource, sample_index)?,
+                    volume: BigEndian::read_u16(&header[2..]).min(MAX_VOLUME.into()) as u8,
+                }),
+            });
+        }
+
+        Ok(Self {
+            delay: BigEndian::read_u16(&data[0..]),
+            instruments,
+            order_count: BigEndian::read_u16(&data[MODULE_ORDER_COUNT_OFFSET..]) as u8,
+            orders: data[MODULE_ORDERS_OFFSET..MODULE_ORDERS_OFFSET + MODULE_ORDERS_LEN].into(),
+            patterns: data[MODULE_PATTERNS_OFFSET..].into(),
+        })
+    }
+}
+
+/// A replacement soundtrack decoded from an OGG Vorbis file and mixed down
+/// to mono.
+#[derive(Clone)]
+pub struct Track {
+    data: Arc<[i16]>,
+    rate: u32,
+}
+
+impl Track {
+    pub fn load(path: &Path) -> Result<Self, AudioError> {
+        let file = File::open(path).map_err(|e| AudioError::TrackOpen(path.to_path_buf(), e))?;
+        let decode_error = |e| AudioError::TrackDecode(path.to_path_buf(), e);
+        let mut reader = OggStreamReader::new(file).map_err(decode_error)?;
+        let channels = usize::from(reader.ident_hdr.audio_channels.max(1));
+        let mut data = Vec::new();
+        while let Some(packet) = reader.read_dec_packet_itl().map_err(decode_error)? {
+            data.extend(packet.chunks_exact(channels).map(|frame| {
+                let sum: i32 = frame.iter().copied().map(i32::from).sum();
+                (sum / channels as i32) as i16
+            }));
+        }
+        if data.is_empty() {
+            return Err(AudioError::EmptyTrack(path.to_path_buf()));
+        }
+        Ok(Self {
+            data: data.into(),
+            rate: reader.ident_hdr.audio_sample_rate,
+        })
+    }
+}
+
+/// A directory of OGG Vorbis files named after the music resource they
+/// replace, like `0x07.ogg`. Tracks are decoded the first time they play.
+pub struct MusicPack {
+    dir: PathBuf,
+    tracks: HashMap<usize, Option<Track>>,
+}
+
+impl MusicPack {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            tracks: HashMap::new(),
+        }
+    }
+
+    /// The track replacing music resource `index`, or `None` when the pack
+    /// has no usable file for it and the module should play instead.
+    pub fn track(&mut self, index: usize) -> Option<Track> {
+        let dir = &self.dir;
+        self.tracks
+            .entry(index)
+            .or_insert_with(|| {
+                let path = find_file(dir, &format!("{index:#04x}.ogg"));
+                if !path.is_file() {
+                    return None;
+                }
+                info!("Playing music {index:#04X} from {}", path.display());
+                Track::load(&path)
+                    .inspect_err(|e| warn!("Falling back to the original music: {e:?}"))
+                    .ok()
+            })
+            .clone()
+    }
+}
+
+/// What `op_play_sound` asks of one voice.
+pub enum SoundRequest {
+    Play {
+        sample: Sample,
+        frequency: u32,
+        volume: u8,
+    },
+    Stop,
+}
+
+/// What `op_play_music` asks of the sequencer. A `delay` of 0 keeps the one
+/// stored in the module. With a `track`, the module still runs for its sync
+/// marks but its notes are left silent while the track loops.
+pub enum MusicRequest {
+    Play {
+        module: Module,
+        track: Option<Track>,
+        delay: u16,
+        position: u8,
+    },
+    SetDelay(u16),
+    Stop,
+}
+
+/// What started a voice, so music and sound effects can be muted apart.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Origin {
+    Music,
+    /// `op_play_sound` on the given VM channel.
+    Sfx(usize),
+}
+
+#[derive(Copy, Clone, Default)]
+struct Mutes {
+    music: bool,
+    sfx: bool,
+    /// Set while the game runs slower than real time, where both would
+    /// come out distorted.
+    slowed: bool,
+}
+
+impl Mutes {
+    fn silences(self, origin: Origin) -> bool {
+        self.slowed
+            || match origin {
+                Origin::Music => self.music,
+                Origin::Sfx(_) => self.sfx,
+            }
+    }
+}
+
+struct Voice {
+    sample: Sample,
+    /// Read position in 16.16 fixed point.
+    position: u64,
+    step: u64,
+    volume: u8,
+    origin: Origin,
+}
+
+impl Voice {
+    fn new(sample: Sample, frequency: u32, volume: u8, origin: Origin, rate: u32) -> Self {
+        Self {
+            sample,
+            position: 0,
+            step: (u64::from(frequency) << 16) / u64::from(rate),
+            volume: volume.min(MAX_VOLUME),
+            origin,
+        }
+    }
+
+    /// Next sample scaled by the voice volume, or `None` once a sample
+    /// without a loop has been played through.
+    fn next(&mut self) -> Option<i32> {
+        let len = self.sample.data.len();
+        let mut index = (self.position >> 16) as usize;
+        if index >= len {
+            if !self.sample.has_loop() {
+                return None;
+            }
+            let loop_len = len - self.sample.loop_start;
+            index = self.sample.loop_start + (index - len) % loop_len;
+            self.position = ((index as u64) << 16) | (self.position & 0xFFFF);
+        }
+        self.position += self.step;
+        Some(i32::from(self.sample.data[index]) * i32::from(self.volume))
+    }
+}
+
+struct TrackVoice {
+    track: Track,
+    /// Read position in 16.16 fixed point.
+    position: u64,
+    step: u64,
+}
+
+impl TrackVoice {
+    fn new(track: Track, rate: u32) -> Self {
+        Self {
+            step: (u64::from(track.rate) << 16) / u64::from(rate),
+            track,
+            position: 0,
+        }
+    }
+
+    fn next(&mut self) -> i32 {
+        let len = self.track.data.len() as u64;
+        self.position %= len << 16;
+        let value = self.track.data[(self.position >> 16) as usize];
+        self.position += self.step;
+        i32::from(value)
+    }
+}
+
+struct Sequencer {
+    module: Module,
+    delay: u16,
+    order: u8,
+    row: usize,
+    frames_to_row: u32,
+}
+
+impl Sequencer {
+    /// Output frames per pattern row. The original timer fired every
+    /// `delay * 60 / 7050` milliseconds.
+    fn row_frames(&self, rate: u32) -> u32 {
+        let frames = u64::from(rate) * u64::from(self.delay) * 60 / 7_050_000;
+        (frames as u32).max(1)
+    }
+
+    fn pattern_at(&self, order: u8) -> usize {
+        self.module
+            .orders
+            .get(order as usize)
+            .copied()
+            .unwrap_or_default() as usize
+    }
+}
+
+struct MixerState {
+    rate: u32,
+    voices: [Option<Voice>; NUM_VOICES],
+    sequencer: Option<Sequencer>,
+    track: Option<TrackVoice>,
+    music_mark: Option<i16>,
+    mutes: Mutes,
+    /// Buffers handed to the output device so far.
+    #[cfg(feature = "audio")]
+    buffers_played: u64,
+}
+
+impl MixerState {
+    fn play(&mut self, channel: usize, request: SoundRequest, origin: Origin) {
+        self.voices[channel] = match request {
+            SoundRequest::Play {
+                sample,
+                frequency,
+                volume,
+            } if volume > 0 => Some(Voice::new(sample, frequency, volume, origin, self.rate)),
+            _ => None,
+        };
+    }
+
+    fn next_frame(&mut self) -> i16 {
+        if let Some(sequencer) = self.sequencer.as_mut() {
+            if sequencer.frames_to_row == 0 {
+                sequencer.frames_to_row = sequencer.row_frames(self.rate);
+                self.play_row();
+            }
+            if let Some(sequencer) = self.sequencer.as_mut() {
+                sequencer.frames_to_row -= 1;
+            }
+        }
+
+        // Samples are 8-bit and volumes 6-bit, so four voices stay within
+        // 16 bits and never clip. A track is mixed at half its level to
+        // leave room for the sound effects. Muted voices keep advancing so
+        // they are in step when unmuted.
+        let mutes = self.mutes;
+        let voices: i32 = self
+            .voices
+            .iter_mut()
+            .map(|slot| {
+                let Some(voice) = slot.as_mut() else {
+                    return 0;
+                };
+                match voice.next() {
+                    Some(value) if !mutes.silences(voice.origin) => value,
+                    Some(_) => 0,
+                    None => {
+                        *slot = None;
+                        0
+                    }
+                }
+            })
+            .sum();
+        let track = match self.track.as_mut() {
+            Some(track) if !mutes.silences(Origin::Music) => track.next() / 2,
+            Some(track) => {
+                track.next();
+                0
+            }
+            None => 0,
+        };
+        let mix = voices + track;
+        mix.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+
+    /// Plays one row of the current pattern, one note per voice, as the
+    /// original sequencer did.
+    fn play_row(&mut self) {
+        let Some(sequencer) = self.sequencer.as_mut() else {
+            return;
+        };
+        let pattern = sequencer.pattern_at(sequencer.order);
+        let start = pattern * PATTERN_LEN + sequencer.row;
+        let Some(row) = sequencer.module.patterns.get(start..start + ROW_LEN) else {
+            self.sequencer = None;
+            return;
+        };
+        let row: [u8; ROW_LEN] = row.try_into().unwrap_or([0; ROW_LEN]);
+        let module = sequencer.module.clone();
+
+        sequencer.row += ROW_LEN;
+        if sequencer.row >= PATTERN_LEN {
+            sequencer.row = 0;
+            sequencer.order += 1;
+            if sequencer.order >= sequencer.module.order_count {
+                self.sequencer = None;
+            }
+        }
+
+        for (channel, note) in row.chunks_exact(4).enumerate() {
+            self.play_note(channel, &module, note);
+        }
+    }
+
+    fn play_note(&mut self, channel: usize, module: &Module, note: &[u8]) {
+        let period = BigEndian::read_u16(note);
+        let argument = BigEndian::read_u16(&note[2..]);
+        match period {
+            0 => {}
+            NOTE_MARK => self.music_mark = Some(argument as i16),
+            _ if self.track.is_some() => {}
+            NOTE_STOP => self.voices[channel] = None,
+            MIN_PERIOD.. => {
+                let instrument = (argument >> 12) as usize;
+                let Some(Some(instrument)) = instrument
+                    .checked_sub(1)
+                    .and_then(|i| module.instruments.get(i))
+                else {
+                    return;
+                };
+                let change = (argument & 0xFF) as u8;
+                let volume = match (argument >> 8) & 0x0F {
+                    5 => instrument.volume.saturating_add(change).min(MAX_VOLUME),
+                    6 => instrument.volume.saturating_sub(change),
+                    _ => instrument.volume,
+                };
+                let request = SoundRequest::Play {
+                    sample: instrument.sample.clone(),
+                    frequency: PAULA_CLOCK / (u32::from(period) * 2),
+                    volume,
+                };
+                self.play(channel, request, Origin::Music);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Four voices shared by sound effects and music, mixed to mono 16-bit.
+/// Until `open_output` succeeds nothing consumes the mix, so voices never
+/// advance and the music mark is never set, which keeps headless runs
+/// deterministic.
+pub struct Mixer {
+    state: Arc<Mutex<MixerState>>,
+    music_pack: Option<MusicPack>,
+    #[cfg(feature = "audio")]
+    stream: Option<cpal::Stream>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MixerState {
+                rate: DEFAULT_OUTPUT_RATE,
+                voices: Default::default(),
+                sequencer: None,
+                track: None,
+                music_mark: None,
+                mutes: Mutes::default(),
+                #[cfg(feature = "audio")]
+                buffers_played: 0,
+            })),
+            music_pack: None,
+            #[cfg(feature = "audio")]
+            stream: None,
+        }
+    }
+
+    /// Starts playing through the default output device. Without the `audio`
+    /// feature, or when no device can be opened, the game runs silent.
+    pub fn open_output(&mut self) {
+        #[cfg(feature = "audio")]
+        match output::open(Arc::clone(&self.state)) {
+            Ok(stream) => self.stream = Some(stream),
+            Err(e) => warn!("Audio output unavailable: {e}"),
+        }
+        #[cfg(not(feature = "audio"))]
+        warn!("Built without the audio feature, running silent");
+    }
+
+    pub fn set_music_pack(&mut self, music_pack: Option<MusicPack>) {
+        self.music_pack = music_pack;
+    }
+
+    /// The music pack track to play in place of music resource `index`.
+    pub fn replacement_track(&mut self, index: usize) -> Option<Track> {
+        self.music_pack.as_mut()?.track(index)
+    }
+
+    fn state(&self) -> MutexGuard<'_, MixerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn play_sound(&self, channel: u8, request: SoundRequest, owner: usize) {
+        self.state()
+            .play(channel as usize % NUM_VOICES, request, Origin::Sfx(owner));
+    }
+
+    pub fn set_music_muted(&self, muted: bool) {
+        self.state().mutes.music = muted;
+    }
+
+    pub fn set_sfx_muted(&self, muted: bool) {
+        self.state().mutes.sfx = muted;
+    }
+
+    /// Silences everything without touching the music and sound effect
+    /// mutes, for slow motion.
+    pub fn set_slowed(&self, slowed: bool) {
+        self.state().mutes.slowed = slowed;
+    }
+
+    /// Flips the music mute and returns whether music is now muted.
+    pub fn toggle_music_muted(&self) -> bool {
+        let mutes = &mut self.state().mutes;
+        mutes.music = !mutes.music;
+        mutes.music
+    }
+
+    /// Flips the sound effect mute and returns whether effects are now
+    /// muted.
+    pub fn toggle_sfx_muted(&self) -> bool {
+        let mutes = &mut self.state().mutes;
+        mutes.sfx = !mutes.sfx;
+        mutes.sfx
+    }
+
+    pub fn play_music(&self, request: MusicRequest) {
+        let mut state = self.state();
+        match request {
+            MusicRequest::Play {
+                module,
+                track,
+                delay,
+                position,
+            } => {
+                let rate = state.rate;
+                state.track = track.map(|track| TrackVoice::new(track, rate));
+                let delay = if delay == 0 { module.delay } else { delay };
+                state.sequencer = Some(Sequencer {
+                    module,
+                    delay,
+                    order: position,
+                    row: 0,
+                    frames_to_row: 0,
+                });
+            }
+            MusicRequest::SetDelay(delay) => {
+                if let Some(sequencer) = state.sequencer.as_mut() {
+                    sequencer.delay = delay;
+                }
+            }
+            MusicRequest::Stop => {
+                state.sequencer = None;
+                state.track = None;
+            }
+        }
+    }
+
+    /// Silences the sound effects started by the given VM channels.
+    pub fn stop_owned_by(&self, channels: RangeInclusive<usize>) {
+        for slot in self.state().voices.iter_mut() {
+            if slot.as_ref().is_some_and(
+                |voice| matches!(voice.origin, Origin::Sfx(owner) if channels.contains(&owner)),
+            ) {
+                *slot = None;
+            }
+        }
+    }
+
+    pub fn stop_all(&self) {
+        let mut state = self.state();
+        state.voices = Default::default();
+        state.sequencer = None;
+        state.track = None;
+    }
+
+    /// Mixes the next `out.len()` mono frames at the output rate, advancing
+    /// the voices and the music.
+    pub fn render(&self, out: &mut [i16]) {
+        let mut state = self.state();
+        out.iter_mut().for_each(|frame| *frame = state.next_frame());
+    }
+
+    /// Value of the last sync note reached by the music, once.
+    pub fn take_music_mark(&self) -> Option<i16> {
+        self.state().music_mark.take()
+    }
+}
+
+/// Stops every voice before the stream closes, so quitting fades to
+/// silence instead of cutting a wave off with a click.
+impl Drop for Mixer {
+    fn drop(&mut self) {
+        self.stop_all();
+        #[cfg(feature = "audio")]
+        if let Some(stream) = self.stream.take() {
+            output::close(&self.state, stream);
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+mod output {
+    use std::{
+        error::Error,
+        sync::{Arc, Mutex, PoisonError},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use cpal::{
+        SampleFormat,
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+    };
+    use log::warn;
+
+    use super::MixerState;
+
+    pub(super) fn open(state: Arc<Mutex<MixerState>>) -> Result<cpal::Stream, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no output device")?;
+        let supported = device.default_output_config()?;
+        if supported.sample_format() != SampleFormat::F32 {
+            return Err(format!("unsupported sample format {}", supported.sample_format()).into());
+        }
+        let config = supported.config();
+        let channels = config.channels as usize;
+        state.lock().unwrap_or_else(PoisonError::into_inner).rate = config.sample_rate.0;
+
+        let stream = device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _| {
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                for frame in data.chunks_mut(channels) {
+                    frame.fill(f32::from(state.next_frame()) / 32768.0);
+                }
+                state.buffers_played += 1;
+            },
+            |e| warn!("Audio stream error: {e}"),
+            None,
+        )?;
+        stream.play()?;
+        Ok(stream)
+    }
+
+    /// Waits for the device to take a buffer of silence after the voices
+    /// were stopped, then another one so the silent buffer is playing
+    /// rather than queued, before dropping `stream`. Gives up after
+    /// `CLOSE_TIMEOUT` if the device stalled.
+    pub(super) fn close(state: &Mutex<MixerState>, stream: cpal::Stream) {
+        const CLOSE_TIMEOUT: Duration = Duration::from_millis(200);
+        let buffers_played = || {
+            state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .buffers_played
+        };
+        let (start, deadline) = (buffers_played(), Instant::now() + CLOSE_TIMEOUT);
+        while buffers_played() < start + 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(stream);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// One pattern whose first row plays instrument 1 on voice 0 and sets
+    /// sync mark 7 on voice 1.
+    fn module() -> Module {
+        let mut patterns = vec![0; PATTERN_LEN];
+        patterns[..8].copy_from_slice(&[0x01, 0x00, 0x10, 0x00, 0xFF, 0xFD, 0x00, 0x07]);
+        Module {
+            delay: 1,
+            instruments: vec![Some(Instrument {
+                sample: Sample {
+                    data: vec![100; 8].into(),
+                    loop_start: 0,
+                },
+                volume: MAX_VOLUME,
+            })],
+            order_count: 1,
+            orders: vec![0; MODULE_ORDERS_LEN].into(),
+            patterns: patterns.into(),
+        }
+    }
+
+    fn first_frame(track: Option<Track>) -> (i16, Option<i16>) {
+        let mixer = Mixer::new();
+        mixer.play_music(MusicRequest::Play {
+            module: module(),
+            track,
+            delay: 0,
+            position: 0,
+        });
+        let mut out = [0; 1];
+        mixer.render(&mut out);
+        (out[0], mixer.take_music_mark())
+    }
+
+    #[test]
+    fn module_plays_its_notes() {
+        assert_eq!(first_frame(None), (100 * MAX_VOLUME as i16, Some(7)));
+    }
+
+    #[test]
+    fn track_replaces_module_notes_but_keeps_marks() {
+        let track = Track {
+            data: vec![1000; 4].into(),
+            rate: DEFAULT_OUTPUT_RATE,
+        };
+        assert_eq!(first_frame(Some(track)), (500, Some(7)));
+    }
+
+    #[test]
+    fn mutes_apply_by_origin() {
+        let mixer = Mixer::new();
+        mixer.play_music(MusicRequest::Play {
+            module: module(),
+            track: None,
+            delay: 0,
+            position: 0,
+        });
+        let sample = Sample {
+            data: vec![10; 8].into(),
+            loop_start: 0,
+        };
+        let request = SoundRequest::Play {
+            sample,
+            frequency: DEFAULT_OUTPUT_RATE,
+            volume: 1,
+        };
+        mixer.play_sound(2, request, 5);
+        let mut out = [0; 1];
+
+        mixer.set_music_muted(true);
+        mixer.render(&mut out);
+        assert_eq!(out[0], 10);
+
+        mixer.set_music_muted(false);
+        assert!(mixer.toggle_sfx_muted());
+        mixer.render(&mut out);
+        assert_eq!(out[0], 100 * MAX_VOLUME as i16);
+    }
+
+    #[test]
+    fn slowed_mixer_is_silent() {
+        let mixer = Mixer::new();
+        mixer.set_slowed(true);
+        let track = Track {
+            data: vec![1000; 4].into(),
+            rate: DEFAULT_OUTPUT_RATE,
+        };
+        mixer.play_music(MusicRequest::Play {
+            module: module(),
+            track: Some(track),
+            delay: 0,
+            position: 0,
+        });
+        let mut out = [0; 1];
+        mixer.render(&mut out);
+        assert_eq!(out[0], 0);
+        assert_eq!(mixer.take_music_mark(), Some(7));
+    }
+
+    #[test]
+    fn music_pack_without_file_falls_back() {
+        let dir = env::temp_dir().join(format!("arw-music-pack-{}", std::process::id()));
+        assert!(MusicPack::new(dir).track(0x07).is_none());
+    }
+}
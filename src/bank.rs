@@ -1,21 +1,38 @@
 use std::{
-    fs::File,
+    collections::HashMap,
+    fs::{self, File},
     io::{self, Read, Seek, SeekFrom},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use byteorder::{BigEndian, ReadBytesExt};
 use iter_read::IterRead;
 use thiserror::Error;
 
-use crate::mem_entry::MemEntry;
+use crate::{mem_entry::MemEntry, resource::DEFAULT_MEM_LIST_FILE};
 
 #[derive(Error, Debug)]
 pub enum BankError {
     #[error("Error while opening bank file")]
-    OnOpen(io::Error),
+    OnOpen(#[source] io::Error),
     #[error("IO error while reading bank")]
-    Io(io::Error),
+    Io(#[source] io::Error),
+    #[error("No bank {0:02x}")]
+    NoSuchBank(u8),
+    #[error("Packed size {packed_size} exceeds unpacked size {size}")]
+    InvalidPackedSize { packed_size: u32, size: u32 },
+    #[error("Declared unpacked size {declared} exceeds the expected {expected} bytes")]
+    UnpackedSizeMismatch { declared: i32, expected: usize },
+    #[error("Bank {name} holds {len} bytes but the entry ends at {end}")]
+    OutOfBounds { name: String, end: u64, len: u64 },
+    #[error("Back-reference {distance} bytes back with only {available} bytes unpacked")]
+    InvalidReference { distance: usize, available: usize },
+    #[error("Unpacked data grows past the {limit} bytes cap")]
+    OutputOverflow { limit: usize },
+    #[error("Checksum left at {0:#010X} after unpacking")]
+    CrcMismatch(u32),
+    #[error("Unpacked {actual} bytes instead of {expected}")]
+    UnpackedLengthMismatch { actual: usize, expected: usize },
 }
 
 impl From<io::Error> for BankError {
@@ -24,23 +41,200 @@ impl From<io::Error> for BankError {
     }
 }
 
+/// Where the memlist and packed resources are read from.
+/// `DirectoryBankSource` serves the files of a data directory,
+/// `MemoryBankSource` serves in-memory data so the engine can run without a
+/// filesystem, e.g. on data fetched by a browser frontend.
+pub trait BankSource {
+    fn read_mem_list(&self) -> io::Result<Vec<u8>>;
+    fn bank_len(&self, bank_id: u8) -> Result<u64, BankError>;
+    fn read_at(&mut self, bank_id: u8, offset: u64, buf: &mut [u8]) -> Result<(), BankError>;
+
+    /// An independent source over the same banks that can be moved to a
+    /// background thread, if the source supports it.
+    fn detached(&self) -> Option<Box<dyn BankSource + Send>> {
+        None
+    }
+}
+
+fn bank_name(bank_id: u8) -> String {
+    format!("bank{bank_id:02x}")
+}
+
+/// Path of `name` in `dir`, falling back to a case-insensitive match so
+/// dumps with uppercase file names also load on case-sensitive filesystems.
+pub(crate) fn find_file(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if path.exists() {
+        return path;
+    }
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name())
+        .find(|file_name| {
+            file_name
+                .to_str()
+                .is_some_and(|file_name| file_name.eq_ignore_ascii_case(name))
+        })
+        .map_or(path, |file_name| dir.join(file_name))
+}
+
+pub struct DirectoryBankSource {
+    data_dir: PathBuf,
+    mem_list_file: String,
+}
+
+impl DirectoryBankSource {
+    pub fn new(data_dir: &Path) -> Self {
+        Self::with_mem_list(data_dir, DEFAULT_MEM_LIST_FILE)
+    }
+
+    pub fn with_mem_list(data_dir: &Path, mem_list_file: &str) -> Self {
+        Self {
+            data_dir: data_dir.to_path_buf(),
+            mem_list_file: mem_list_file.to_string(),
+        }
+    }
+
+    fn bank_path(&self, bank_id: u8) -> PathBuf {
+        find_file(&self.data_dir, &bank_name(bank_id))
+    }
+}
+
+impl BankSource for DirectoryBankSource {
+    fn read_mem_list(&self) -> io::Result<Vec<u8>> {
+        fs::read(find_file(&self.data_dir, &self.mem_list_file))
+    }
+
+    fn detached(&self) -> Option<Box<dyn BankSource + Send>> {
+        Some(Box::new(Self::with_mem_list(
+            &self.data_dir,
+            &self.mem_list_file,
+        )))
+    }
+
+    fn bank_len(&self, bank_id: u8) -> Result<u64, BankError> {
+        let metadata = fs::metadata(self.bank_path(bank_id)).map_err(BankError::OnOpen)?;
+        Ok(metadata.len())
+    }
+
+    fn read_at(&mut self, bank_id: u8, offset: u64, buf: &mut [u8]) -> Result<(), BankError> {
+        let mut file = File::open(self.bank_path(bank_id)).map_err(BankError::OnOpen)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryBankSource {
+    mem_list: Option<Vec<u8>>,
+    banks: HashMap<u8, Vec<u8>>,
+}
+
+impl MemoryBankSource {
+    pub fn set_mem_list(&mut self, data: Vec<u8>) {
+        self.mem_list = Some(data);
+    }
+
+    pub fn insert(&mut self, bank_id: u8, data: Vec<u8>) {
+        self.banks.insert(bank_id, data);
+    }
+}
+
+impl BankSource for MemoryBankSource {
+    fn read_mem_list(&self) -> io::Result<Vec<u8>> {
+        self.mem_list
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no memlist provided"))
+    }
+
+    fn bank_len(&self, bank_id: u8) -> Result<u64, BankError> {
+        self.banks
+            .get(&bank_id)
+            .map(|bank| bank.len() as u64)
+            .ok_or(BankError::NoSuchBank(bank_id))
+    }
+
+    fn read_at(&mut self, bank_id: u8, offset: u64, buf: &mut [u8]) -> Result<(), BankError> {
+        let bank = self
+            .banks
+            .get(&bank_id)
+            .ok_or(BankError::NoSuchBank(bank_id))?;
+        let end = offset + buf.len() as u64;
+        let data = bank
+            .get(offset as usize..end as usize)
+            .ok_or(BankError::OutOfBounds {
+                name: bank_name(bank_id),
+                end,
+                len: bank.len() as u64,
+            })?;
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+}
+
 pub struct BankReader {}
 
 impl BankReader {
-    pub fn read_bank(data_dir: &Path, mem_entry: &mut MemEntry) -> Result<Vec<u8>, BankError> {
-        let name = format!("bank{:02x}", mem_entry.bank_id);
-        let mut file = File::open(data_dir.join(&name)).map_err(BankError::OnOpen)?;
+    pub(crate) fn read_bank(
+        source: &mut dyn BankSource,
+        mem_entry: &MemEntry,
+    ) -> Result<Vec<u8>, BankError> {
+        Self::check_sizes(mem_entry)?;
 
-        file.seek(SeekFrom::Start(mem_entry.bank_offset.into()))?;
         let mut buf = vec![0; mem_entry.packed_size as usize];
-        file.read_exact(&mut buf)?;
+        source.read_at(mem_entry.bank_id, mem_entry.bank_offset.into(), &mut buf)?;
 
         if mem_entry.packed_size == mem_entry.size {
             return Ok(buf);
         }
 
-        let mut unpacker = Unpacker::new(IterRead::new(buf.chunks(4).rev().flatten()));
-        Ok(unpacker.unpack()?)
+        Self::unpack(&buf, mem_entry.size as usize)
+    }
+
+    /// Unpacks a packed resource, which is decoded backwards one big-endian
+    /// word at a time, and checks it yields exactly `size` bytes.
+    pub(crate) fn unpack(packed: &[u8], size: usize) -> Result<Vec<u8>, BankError> {
+        let mut unpacker = Unpacker::new(IterRead::new(packed.chunks(4).rev().flatten()));
+        let data = unpacker.unpack(size)?;
+        if data.len() != size {
+            return Err(BankError::UnpackedLengthMismatch {
+                actual: data.len(),
+                expected: size,
+            });
+        }
+        Ok(data)
+    }
+
+    pub(crate) fn check_bank(
+        source: &dyn BankSource,
+        mem_entry: &MemEntry,
+    ) -> Result<(), BankError> {
+        let len = source.bank_len(mem_entry.bank_id)?;
+        Self::check_sizes(mem_entry)?;
+
+        let end = u64::from(mem_entry.bank_offset) + u64::from(mem_entry.packed_size);
+        if end > len {
+            return Err(BankError::OutOfBounds {
+                name: bank_name(mem_entry.bank_id),
+                end,
+                len,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_sizes(mem_entry: &MemEntry) -> Result<(), BankError> {
+        if mem_entry.packed_size > mem_entry.size {
+            return Err(BankError::InvalidPackedSize {
+                packed_size: mem_entry.packed_size,
+                size: mem_entry.size,
+            });
+        }
+        Ok(())
     }
 }
 
@@ -64,13 +258,22 @@ impl<I: Read> Unpacker<I> {
         }
     }
 
+    fn reserve(output: &[u8], length: u16, limit: usize) -> Result<(), BankError> {
+        if output.len() + length as usize > limit {
+            return Err(BankError::OutputOverflow { limit });
+        }
+        Ok(())
+    }
+
     fn decode_literal(
         &mut self,
         bit_length: u8,
         additional_length: u8,
         output: &mut Vec<u8>,
-    ) -> Result<(), io::Error> {
+        limit: usize,
+    ) -> Result<(), BankError> {
         let length: u16 = self.get_code(bit_length)? + additional_length as u16 + 1;
+        Self::reserve(output, length, limit)?;
         for _ in 0..length {
             let data = self.get_code(8)? as u8;
             output.push(data);
@@ -84,24 +287,42 @@ impl<I: Read> Unpacker<I> {
         bit_length: u8,
         length: u16,
         output: &mut Vec<u8>,
-    ) -> Result<(), io::Error> {
-        let offset = output.len() as u16 - self.get_code(bit_length)?;
-        for i in 0..length {
-            let data: u8 = output.get((offset + i) as usize).copied().unwrap_or(0u8);
+        limit: usize,
+    ) -> Result<(), BankError> {
+        let distance = self.get_code(bit_length)? as usize;
+        Self::reserve(output, length, limit)?;
+        let offset = output
+            .len()
+            .checked_sub(distance)
+            .ok_or(BankError::InvalidReference {
+                distance,
+                available: output.len(),
+            })?;
+        for i in 0..length as usize {
+            let data: u8 = output.get(offset + i).copied().unwrap_or(0u8);
             output.push(data);
         }
         self.ctx.datasize -= length as i32;
         Ok(())
     }
 
-    pub fn unpack(&mut self) -> Result<Vec<u8>, io::Error> {
+    /// Unpacks at most `max_size` bytes. The output buffer is allocated once
+    /// up front and the decode fails as soon as it would grow past the cap,
+    /// so corrupt data cannot cause unbounded allocation.
+    pub fn unpack(&mut self, max_size: usize) -> Result<Vec<u8>, BankError> {
         let ctx = &mut self.ctx;
         ctx.datasize = self.reader.read_i32::<BigEndian>()?;
+        if ctx.datasize as i64 > max_size as i64 {
+            return Err(BankError::UnpackedSizeMismatch {
+                declared: ctx.datasize,
+                expected: max_size,
+            });
+        }
         ctx.crc = self.reader.read_u32::<BigEndian>()?;
         ctx.chk = self.reader.read_u32::<BigEndian>()?;
         ctx.crc ^= ctx.chk;
 
-        let mut output = vec![];
+        let mut output = Vec::with_capacity(max_size.min(self.ctx.datasize.max(0) as usize));
         loop {
             if self.ctx.datasize <= 0 {
                 break;
@@ -109,23 +330,28 @@ impl<I: Read> Unpacker<I> {
 
             if self.get_next_bit()? == 0 {
                 if self.get_next_bit()? == 0 {
-                    self.decode_literal(3, 0, &mut output)?
+                    self.decode_literal(3, 0, &mut output, max_size)?
                 } else {
-                    self.decode_reference(8, 2, &mut output)?
+                    self.decode_reference(8, 2, &mut output, max_size)?
                 }
             } else {
                 let code = self.get_code(2)?;
                 if code == 3 {
-                    self.decode_literal(8, 8, &mut output)?;
+                    self.decode_literal(8, 8, &mut output, max_size)?;
                 } else if code < 2 {
-                    self.decode_reference(code as u8 + 9, code + 3, &mut output)?;
+                    self.decode_reference(code as u8 + 9, code + 3, &mut output, max_size)?;
                 } else {
                     let length = self.get_code(8)? + 1;
-                    self.decode_reference(12, length, &mut output)?;
+                    self.decode_reference(12, length, &mut output, max_size)?;
                 }
             }
         }
 
+        // Every word read was XORed into the checksum, which cancels out to
+        // zero for an intact stream.
+        if self.ctx.crc != 0 {
+            return Err(BankError::CrcMismatch(self.ctx.crc));
+        }
         output.reverse();
         Ok(output)
     }
@@ -158,3 +384,77 @@ impl<I: Read> Unpacker<I> {
         lsb
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{PACKED, UNPACKED};
+
+    /// `PACKED` with `patch` applied to its bytes.
+    fn patched(patch: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let mut packed = PACKED.to_vec();
+        patch(&mut packed);
+        packed
+    }
+
+    #[test]
+    fn unpacks_embedded_resource() {
+        let data = BankReader::unpack(PACKED, UNPACKED.len()).unwrap();
+        assert_eq!(data, UNPACKED);
+    }
+
+    #[test]
+    fn rejects_packed_size_past_size() {
+        let entry = MemEntry {
+            status: 0,
+            bank_id: 1,
+            bank_offset: 0,
+            packed_size: 10,
+            size: 5,
+        };
+        let result = BankReader::read_bank(&mut MemoryBankSource::default(), &entry);
+        assert!(matches!(
+            result,
+            Err(BankError::InvalidPackedSize {
+                packed_size: 10,
+                size: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupt_checksum() {
+        // The checksum word sits just before the trailing size word.
+        let packed = patched(|packed| {
+            let len = packed.len();
+            packed[len - 5] ^= 1;
+        });
+        let result = BankReader::unpack(&packed, UNPACKED.len());
+        assert!(matches!(result, Err(BankError::CrcMismatch(1))));
+    }
+
+    #[test]
+    fn rejects_output_past_declared_size() {
+        let packed = patched(|packed| {
+            let len = packed.len();
+            packed[len - 1] = 54;
+        });
+        let result = BankReader::unpack(&packed, 54);
+        assert!(matches!(
+            result,
+            Err(BankError::OutputOverflow { limit: 54 })
+        ));
+    }
+
+    #[test]
+    fn rejects_short_output() {
+        let result = BankReader::unpack(PACKED, UNPACKED.len() + 5);
+        assert!(matches!(
+            result,
+            Err(BankError::UnpackedLengthMismatch {
+                actual: 55,
+                expected: 60
+            })
+        ));
+    }
+}
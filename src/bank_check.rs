@@ -0,0 +1,50 @@
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+use crate::{engine::EngineError, resource::ResourceRegistry};
+
+pub struct BankCheckReport {
+    entries: usize,
+    failures: Vec<(usize, String)>,
+}
+
+impl BankCheckReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for BankCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in &self.failures {
+            writeln!(f, "entry {index:02X}: {error}")?;
+        }
+        write!(
+            f,
+            "{} entries checked, {} good, {} bad",
+            self.entries,
+            self.entries - self.failures.len(),
+            self.failures.len()
+        )
+    }
+}
+
+/// Reads and unpacks every memlist entry, collecting those whose checksum
+/// or unpacked size does not match.
+pub fn run(data_dir: &Path, mem_list_file: &str) -> Result<BankCheckReport, EngineError> {
+    let mut resource = ResourceRegistry::new(data_dir.to_path_buf(), mem_list_file);
+    resource.read_entries()?;
+
+    let entries = resource.entry_count();
+    let failures = (0..entries)
+        .filter_map(|index| {
+            resource
+                .load_entry(index)
+                .err()
+                .map(|e| (index, format!("{e:?}")))
+        })
+        .collect();
+    Ok(BankCheckReport { entries, failures })
+}
@@ -0,0 +1,120 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    engine::{Engine, EngineConfig, EngineError},
+    headless::HeadlessRenderer,
+    input::InputState,
+    vm::FrameCounters,
+};
+
+const BENCHMARK_SEED: i16 = 0;
+const PERCENTILES: [usize; 3] = [50, 95, 99];
+
+pub struct BenchmarkReport {
+    frame_times: Vec<Duration>,
+    counters: FrameCounters,
+}
+
+impl BenchmarkReport {
+    fn total(&self) -> Duration {
+        self.frame_times.iter().sum()
+    }
+
+    /// p50, p95 and p99 frame times, using the nearest-rank method.
+    pub fn percentiles(&self) -> FramePercentiles {
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_unstable();
+        let values = PERCENTILES.map(|percentile| {
+            let rank = (sorted.len() * percentile).div_ceil(100);
+            let value = sorted.get(rank.saturating_sub(1)).copied();
+            (percentile, value.unwrap_or_default())
+        });
+        FramePercentiles { values }
+    }
+}
+
+pub struct FramePercentiles {
+    values: [(usize, Duration); PERCENTILES.len()],
+}
+
+impl Display for FramePercentiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (percentile, value)) in self.values.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let label = format!("p{percentile}:");
+            write!(f, "{label:<9}{:.3} ms", value.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frames = self.frame_times.len();
+        let total = self.total();
+        let average = total.checked_div(frames as u32).unwrap_or_default();
+        let min = self.frame_times.iter().min().copied().unwrap_or_default();
+        let max = self.frame_times.iter().max().copied().unwrap_or_default();
+        writeln!(f, "frames:  {frames}")?;
+        writeln!(f, "total:   {:.3} ms", total.as_secs_f64() * 1000.0)?;
+        writeln!(f, "average: {:.3} ms/frame", average.as_secs_f64() * 1000.0)?;
+        writeln!(f, "min:     {:.3} ms", min.as_secs_f64() * 1000.0)?;
+        writeln!(f, "max:     {:.3} ms", max.as_secs_f64() * 1000.0)?;
+        let per_frame = |count: usize| count as f64 / frames.max(1) as f64;
+        writeln!(f, "opcodes: {:.1}/frame", per_frame(self.counters.opcodes))?;
+        writeln!(
+            f,
+            "runs:    {:.1}/frame",
+            per_frame(self.counters.channels_run)
+        )?;
+        writeln!(
+            f,
+            "draws:   {:.1}/frame",
+            per_frame(self.counters.draw_calls)
+        )?;
+        writeln!(f, "polys:   {:.1}/frame", per_frame(self.counters.polygons))?;
+        writeln!(
+            f,
+            "verts:   {:.1}/frame read",
+            per_frame(self.counters.vertices_read)
+        )?;
+        write!(
+            f,
+            "filled:  {:.1}/frame",
+            per_frame(self.counters.vertices_filled)
+        )
+    }
+}
+
+pub fn run(config: &EngineConfig, frames: usize) -> Result<BenchmarkReport, EngineError> {
+    let mut engine = Engine::new(
+        config,
+        Box::new(HeadlessRenderer::default()),
+        BENCHMARK_SEED,
+    )?;
+    engine.set_uncapped(true);
+
+    let mut frame_times = Vec::with_capacity(frames);
+    let mut counters = FrameCounters::default();
+    for _ in 0..frames {
+        let start = Instant::now();
+        engine.tick(InputState::default())?;
+        frame_times.push(start.elapsed());
+        let frame = engine.frame_counters();
+        counters.opcodes += frame.opcodes;
+        counters.channels_run += frame.channels_run;
+        counters.draw_calls += frame.draw_calls;
+        counters.polygons += frame.polygons;
+        counters.vertices_read += frame.vertices_read;
+        counters.vertices_filled += frame.vertices_filled;
+    }
+    Ok(BenchmarkReport {
+        frame_times,
+        counters,
+    })
+}
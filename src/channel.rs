@@ -1,9 +1,20 @@
+use serde::Serialize;
+
 #[derive(Copy, Clone, Debug)]
 pub enum ProcessCounter {
     Valid(usize),
     Invalid,
 }
 
+impl ProcessCounter {
+    pub fn offset(self) -> Option<usize> {
+        match self {
+            ProcessCounter::Valid(offset) => Some(offset),
+            ProcessCounter::Invalid => None,
+        }
+    }
+}
+
 impl From<u64> for ProcessCounter {
     fn from(value: u64) -> Self {
         if value >= 0xFFFE {
@@ -13,7 +24,8 @@ impl From<u64> for ProcessCounter {
     }
 }
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum State {
     Ready,
     Running,
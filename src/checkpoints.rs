@@ -0,0 +1,71 @@
+use std::fmt::{self, Display};
+
+use crate::parts::GamePart;
+
+/// A place the game can restart from: a part, plus the value of VM
+/// variable 0 its scripts read to pick the starting scene.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub name: &'static str,
+    pub part: GamePart,
+    pub position: i16,
+}
+
+const fn checkpoint(name: &'static str, part: GamePart, position: i16) -> Checkpoint {
+    Checkpoint {
+        name,
+        part,
+        position,
+    }
+}
+
+/// Every restart position the password screen can reach, in game order,
+/// named after the area they are in.
+pub const CHECKPOINTS: &[Checkpoint] = &[
+    checkpoint("water", GamePart::Tree, 10),
+    checkpoint("water-2", GamePart::Tree, 12),
+    checkpoint("water-3", GamePart::Tree, 14),
+    checkpoint("prison", GamePart::Four, 20),
+    checkpoint("prison-2", GamePart::Four, 24),
+    checkpoint("prison-3", GamePart::Four, 26),
+    checkpoint("city", GamePart::Five, 30),
+    checkpoint("city-2", GamePart::Five, 31),
+    checkpoint("city-3", GamePart::Five, 32),
+    checkpoint("city-4", GamePart::Five, 33),
+    checkpoint("city-5", GamePart::Five, 34),
+    checkpoint("city-6", GamePart::Five, 35),
+    checkpoint("city-7", GamePart::Five, 36),
+    checkpoint("city-8", GamePart::Five, 37),
+    checkpoint("city-9", GamePart::Five, 38),
+    checkpoint("city-10", GamePart::Five, 39),
+    checkpoint("city-11", GamePart::Five, 40),
+    checkpoint("city-12", GamePart::Five, 41),
+    checkpoint("city-13", GamePart::Five, 42),
+    checkpoint("city-14", GamePart::Five, 43),
+    checkpoint("city-15", GamePart::Five, 44),
+    checkpoint("city-16", GamePart::Five, 45),
+    checkpoint("city-17", GamePart::Five, 46),
+    checkpoint("city-18", GamePart::Five, 47),
+    checkpoint("city-19", GamePart::Five, 48),
+    checkpoint("city-20", GamePart::Five, 49),
+    checkpoint("arena", GamePart::Six, 50),
+    checkpoint("baths", GamePart::Seven, 60),
+    checkpoint("baths-2", GamePart::Seven, 64),
+    checkpoint("baths-3", GamePart::Seven, 65),
+    checkpoint("baths-4", GamePart::Seven, 66),
+    checkpoint("baths-5", GamePart::Seven, 67),
+    checkpoint("baths-6", GamePart::Seven, 68),
+    checkpoint("final", GamePart::Eigth, 0),
+];
+
+pub fn find(name: &str) -> Option<&'static Checkpoint> {
+    CHECKPOINTS
+        .iter()
+        .find(|checkpoint| checkpoint.name.eq_ignore_ascii_case(name))
+}
+
+impl Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<10} {} at {}", self.name, self.part, self.position)
+    }
+}
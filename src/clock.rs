@@ -0,0 +1,36 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time source for frame pacing. `SystemClock` reads the monotonic clock and
+/// blocks the thread. A frontend that cannot block, such as a browser loop
+/// driven by requestAnimationFrame, can provide its own clock or run the
+/// engine uncapped and pace the ticks itself.
+pub trait Clock {
+    /// Time elapsed since an arbitrary origin, never going backwards.
+    fn now(&self) -> Duration;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
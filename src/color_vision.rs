@@ -0,0 +1,62 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::renderer::NUM_COLORS;
+
+type Matrix = [[f64; 3]; 3];
+
+const RGB_TO_LMS: Matrix = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+const LMS_TO_RGB: Matrix = [
+    [0.0809444479, -0.130504409, 0.116721066],
+    [-0.0102485335, 0.0540193266, -0.113614708],
+    [-0.000365296938, -0.00412161469, 0.693511405],
+];
+const PROTANOPIA: Matrix = [[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+const DEUTERANOPIA: Matrix = [[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]];
+const TRITANOPIA: Matrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]];
+const ERROR_SHIFT: Matrix = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+
+/// Color vision deficiency the palette is daltonized for: the colors lost to
+/// the deficiency are simulated and their difference is shifted into the
+/// channels that remain distinguishable.
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorVision {
+    #[default]
+    None,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl ColorVision {
+    pub fn apply(self, palette: [u32; NUM_COLORS]) -> [u32; NUM_COLORS] {
+        let simulation = match self {
+            ColorVision::None => return palette,
+            ColorVision::Deuteranopia => DEUTERANOPIA,
+            ColorVision::Protanopia => PROTANOPIA,
+            ColorVision::Tritanopia => TRITANOPIA,
+        };
+        palette.map(|color| Self::daltonize(color, &simulation))
+    }
+
+    fn daltonize(color: u32, simulation: &Matrix) -> u32 {
+        let rgb = [16, 8, 0].map(|shift| ((color >> shift) & 0xFF) as f64);
+        let lms = Self::multiply(&RGB_TO_LMS, rgb);
+        let simulated = Self::multiply(&LMS_TO_RGB, Self::multiply(simulation, lms));
+        let error = [0, 1, 2].map(|i| rgb[i] - simulated[i]);
+        let shift = Self::multiply(&ERROR_SHIFT, error);
+        [0, 1, 2].iter().fold(0, |acc, &i| {
+            let channel = (rgb[i] + shift[i]).round().clamp(0.0, 255.0) as u32;
+            (acc << 8) | channel
+        })
+    }
+
+    fn multiply(matrix: &Matrix, vector: [f64; 3]) -> [f64; 3] {
+        matrix.map(|row| row.iter().zip(vector).map(|(a, b)| a * b).sum())
+    }
+}
@@ -0,0 +1,37 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// A fix that can be reverted to the behavior the engine had before it,
+/// to reproduce how a scene used to look or find which fix changed it.
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quirk {
+    /// `copy_page` ignores the 0x80 scroll flag of the source page and
+    /// copies it without scrolling.
+    CopyPageScroll,
+    /// A single-point shape is also rasterized as a polygon after its point
+    /// is drawn instead of returning right away.
+    SinglePointReturn,
+}
+
+/// The set of enabled quirks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quirks(u8);
+
+impl Quirks {
+    pub fn new(quirks: &[Quirk]) -> Self {
+        Self(
+            quirks
+                .iter()
+                .fold(0, |bits, &quirk| bits | Self::bit(quirk)),
+        )
+    }
+
+    pub fn contains(self, quirk: Quirk) -> bool {
+        self.0 & Self::bit(quirk) != 0
+    }
+
+    fn bit(quirk: Quirk) -> u8 {
+        1 << quirk as u8
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::{self, Display};
+
+use crate::opcodes::{OPCODE_NAMES, OPCODE_TABLE};
+
+/// Execution counts for every opcode table entry plus the two draw paths
+/// selected by the 0x80 and 0x40 bits.
+#[derive(Clone)]
+pub struct OpcodeCoverage {
+    table: Vec<u64>,
+    draw_background: u64,
+    draw_sprite: u64,
+}
+
+impl Default for OpcodeCoverage {
+    fn default() -> Self {
+        Self {
+            table: vec![0; OPCODE_TABLE.len()],
+            draw_background: 0,
+            draw_sprite: 0,
+        }
+    }
+}
+
+impl OpcodeCoverage {
+    pub fn record(&mut self, opcode: u8) {
+        match opcode {
+            opcode if opcode & 0x80 != 0 => self.draw_background += 1,
+            opcode if opcode & 0x40 != 0 => self.draw_sprite += 1,
+            opcode => {
+                if let Some(count) = self.table.get_mut(opcode as usize) {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&str, u64)> {
+        OPCODE_NAMES
+            .iter()
+            .copied()
+            .zip(self.table.iter().copied())
+            .chain([
+                ("draw_background", self.draw_background),
+                ("draw_sprite", self.draw_sprite),
+            ])
+    }
+}
+
+impl Display for OpcodeCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hit = self.entries().filter(|&(_, count)| count > 0).count();
+        write!(f, "opcode coverage: {hit}/{}", self.entries().count())?;
+        for (name, count) in self.entries() {
+            let marker = if count > 0 { ' ' } else { '!' };
+            write!(f, "\n{marker} {name:<16} {count}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    io::{self, BufRead},
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Deserializer, de};
+use winit::keyboard::KeyCode;
+
+use crate::vm::Vm;
+
+const OVERLAY_TOGGLE_KEY: KeyCode = KeyCode::F2;
+const MAX_VARIABLE_ID: i32 = 0xFF;
+
+pub enum ConsoleCommand {
+    Print(usize),
+    Poke(usize, i16),
+    Watch(usize),
+    Unwatch(usize),
+}
+
+pub fn parse_number(raw: &str) -> Option<i32> {
+    let raw = raw.trim();
+    let (negative, digits) = match raw.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, raw),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i32::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+pub fn parse_variable_id(raw: &str) -> Option<usize> {
+    parse_number(raw)
+        .filter(|id| (0..=MAX_VARIABLE_ID).contains(id))
+        .map(|id| id as usize)
+}
+
+pub fn parse_assignment(raw: &str) -> Option<(usize, i16)> {
+    let (id, value) = raw.split_once('=')?;
+    let value = parse_number(value)
+        .filter(|&value| (i16::MIN as i32..=u16::MAX as i32).contains(&value))?;
+    Some((parse_variable_id(id)?, value as i16))
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct VariableAssignment {
+    pub id: usize,
+    pub value: i16,
+}
+
+impl FromStr for VariableAssignment {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        parse_assignment(raw)
+            .map(|(id, value)| Self { id, value })
+            .ok_or_else(|| format!("Invalid variable assignment {raw}, expected VAR=VALUE"))
+    }
+}
+
+impl<'de> Deserialize<'de> for VariableAssignment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if let Some(id) = line.strip_prefix("watch ") {
+            return parse_variable_id(id).map(ConsoleCommand::Watch);
+        }
+        if let Some(id) = line.strip_prefix("unwatch ") {
+            return parse_variable_id(id).map(ConsoleCommand::Unwatch);
+        }
+        if line.contains('=') {
+            return parse_assignment(line).map(|(id, value)| ConsoleCommand::Poke(id, value));
+        }
+        parse_variable_id(line).map(ConsoleCommand::Print)
+    }
+}
+
+pub struct Debugger {
+    console: Option<Receiver<String>>,
+    watched: Vec<usize>,
+    overlay_enabled: bool,
+}
+
+impl Debugger {
+    pub fn new(with_console: bool) -> Self {
+        Self {
+            console: with_console.then(Self::spawn_console),
+            watched: Vec::new(),
+            overlay_enabled: false,
+        }
+    }
+
+    fn spawn_console() -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
+    pub fn handle_key(&mut self, key_code: KeyCode) {
+        if key_code == OVERLAY_TOGGLE_KEY {
+            self.overlay_enabled = !self.overlay_enabled;
+        }
+    }
+
+    pub fn process_console(&mut self, vm: &mut Vm) {
+        let Some(console) = &self.console else {
+            return;
+        };
+        for line in console.try_iter() {
+            match ConsoleCommand::parse(&line) {
+                Some(ConsoleCommand::Print(id)) => {
+                    info!("var[0x{id:02X}] = 0x{:04X}", vm.get_variable(id))
+                }
+                Some(ConsoleCommand::Poke(id, value)) => vm.set_variable(id, value),
+                Some(ConsoleCommand::Watch(id)) => {
+                    if !self.watched.contains(&id) {
+                        self.watched.push(id);
+                    }
+                }
+                Some(ConsoleCommand::Unwatch(id)) => self.watched.retain(|&watched| watched != id),
+                None => warn!("Unrecognized debug command: {line}"),
+            }
+        }
+    }
+
+    pub fn overlay(&self, vm: &Vm) -> Vec<String> {
+        if !self.overlay_enabled {
+            return Vec::new();
+        }
+        self.watched
+            .iter()
+            .map(|&id| format!("{id:02X}={:04X}", vm.get_variable(id) as u16))
+            .collect()
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Cursor, Read},
+    path::Path,
+};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+    engine::EngineError,
+    loaded::LoadedPart,
+    opcodes::OPCODE_NAMES,
+    parts::{GamePart, Segment},
+    resource::ResourceRegistry,
+};
+
+const DEFAULT_ZOOM: i16 = 0x40;
+const COMPARISONS: [&str; 6] = ["==", "!=", ">", ">=", "<", "<="];
+
+/// A sprite coordinate or zoom: either encoded in the bytecode or read
+/// from a variable when the opcode runs.
+#[derive(Copy, Clone, Debug)]
+pub enum Operand {
+    Const(i16),
+    Var(u8),
+}
+
+impl Operand {
+    pub fn resolve(self, variables: &[i16]) -> i16 {
+        match self {
+            Operand::Const(value) => value,
+            Operand::Var(id) => variables[id as usize],
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Const(value) => write!(f, "{value}"),
+            Operand::Var(id) => write!(f, "var[{id:#04X}]"),
+        }
+    }
+}
+
+/// Operands of the 0x40 sprite form. The low six opcode bits select how x
+/// (0x00 word, 0x10 variable, 0x20 byte, 0x30 byte + 256), y (0x00 word,
+/// 0x04 variable, 0x08 and 0x0C byte) and zoom (0 none, 1 variable, 2 byte,
+/// 3 none and polygon segment) are encoded, as in the reference interpreter.
+pub struct SpriteOperands {
+    pub offset: u16,
+    pub x: Operand,
+    pub y: Operand,
+    pub zoom: Operand,
+    pub from_polygon: bool,
+}
+
+impl SpriteOperands {
+    pub fn read(opcode: u8, reader: &mut impl Read) -> io::Result<Self> {
+        let offset = reader.read_u16::<BigEndian>()?.wrapping_mul(2);
+        let x_byte = reader.read_u8()?;
+        let x = match opcode & 0x30 {
+            0x00 => Operand::Const(i16::from_be_bytes([x_byte, reader.read_u8()?])),
+            0x10 => Operand::Var(x_byte),
+            0x20 => Operand::Const(x_byte.into()),
+            _ => Operand::Const(i16::from(x_byte) + 0x100),
+        };
+        let y_byte = reader.read_u8()?;
+        let y = match opcode & 0x0C {
+            0x00 => Operand::Const(i16::from_be_bytes([y_byte, reader.read_u8()?])),
+            0x04 => Operand::Var(y_byte),
+            _ => Operand::Const(y_byte.into()),
+        };
+        let zoom = match opcode & 3 {
+            1 => Operand::Var(reader.read_u8()?),
+            2 => Operand::Const(reader.read_u8()?.into()),
+            _ => Operand::Const(DEFAULT_ZOOM),
+        };
+        Ok(Self {
+            offset,
+            x,
+            y,
+            zoom,
+            from_polygon: opcode & 3 == 3,
+        })
+    }
+}
+
+/// Operands of the 0x80 background form: the opcode holds the high bits of
+/// the offset, and y past the bottom row moves the shape right instead.
+pub struct BackgroundOperands {
+    pub offset: u16,
+    pub x: i16,
+    pub y: i16,
+}
+
+impl BackgroundOperands {
+    pub fn read(opcode: u8, reader: &mut impl Read) -> io::Result<Self> {
+        let offset = u16::from_be_bytes([opcode, reader.read_u8()?]).wrapping_mul(2);
+        let mut x = i16::from(reader.read_u8()?);
+        let mut y = i16::from(reader.read_u8()?);
+        let h = y - 199;
+        if h > 0 {
+            y = 199;
+            x += h;
+        }
+        Ok(Self { offset, x, y })
+    }
+}
+
+pub struct Instruction {
+    pub offset: usize,
+    pub mnemonic: &'static str,
+    pub operands: String,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04X}: {:<16} {}",
+            self.offset, self.mnemonic, self.operands
+        )
+    }
+}
+
+/// Decodes a part's bytecode from the start. Works on a copy of the
+/// segment, so the cursor the VM runs from is left where it was.
+pub fn disassemble_part(part: &LoadedPart) -> Vec<Instruction> {
+    disassemble(part.segment(Segment::Bytecode).unwrap_or_default())
+}
+
+/// Decodes `bytecode` up to its end. An instruction cut short by the end of
+/// the segment is reported as `truncated` and ends the listing.
+pub fn disassemble(bytecode: &[u8]) -> Vec<Instruction> {
+    let mut reader = Cursor::new(bytecode);
+    let mut instructions = Vec::new();
+    while (reader.position() as usize) < bytecode.len() {
+        let offset = reader.position() as usize;
+        match decode(&mut reader) {
+            Ok((mnemonic, operands)) => instructions.push(Instruction {
+                offset,
+                mnemonic,
+                operands,
+            }),
+            Err(_) => {
+                instructions.push(Instruction {
+                    offset,
+                    mnemonic: "truncated",
+                    operands: format!("{} bytes left", bytecode.len() - offset),
+                });
+                break;
+            }
+        }
+    }
+    instructions
+}
+
+fn decode(reader: &mut impl Read) -> io::Result<(&'static str, String)> {
+    let opcode = reader.read_u8()?;
+    if opcode & 0x80 != 0 {
+        let BackgroundOperands { offset, x, y } = BackgroundOperands::read(opcode, reader)?;
+        return Ok(("draw_background", format!("{offset:#06X} x={x} y={y}")));
+    }
+    if opcode & 0x40 != 0 {
+        let sprite = SpriteOperands::read(opcode, reader)?;
+        let segment = if sprite.from_polygon { " polygon" } else { "" };
+        return Ok((
+            "draw_sprite",
+            format!(
+                "{:#06X}{segment} x={} y={} zoom={}",
+                sprite.offset, sprite.x, sprite.y, sprite.zoom
+            ),
+        ));
+    }
+
+    let Some(&mnemonic) = OPCODE_NAMES.get(opcode as usize) else {
+        return Ok(("unknown", format!("{opcode:#04X}")));
+    };
+    let var = |reader: &mut dyn Read| reader.read_u8().map(|id| format!("var[{id:#04X}]"));
+    let word = |reader: &mut dyn Read| reader.read_u16::<BigEndian>();
+    let byte = |reader: &mut dyn Read| reader.read_u8();
+    let operands = match opcode {
+        0x00 | 0x03 | 0x14..=0x17 => format!("{}, {:#06X}", var(reader)?, word(reader)?),
+        0x01 | 0x02 | 0x13 => format!("{}, {}", var(reader)?, var(reader)?),
+        0x04 | 0x07 => format!("{:#06X}", word(reader)?),
+        0x08 => format!("ch[{}] -> {:#06X}", byte(reader)?, word(reader)?),
+        0x09 => format!("{} -> {:#06X}", var(reader)?, word(reader)?),
+        0x0A => {
+            let condition = byte(reader)?;
+            let variable = var(reader)?;
+            let operand = match condition {
+                c if c & 0x80 != 0 => var(reader)?,
+                c if c & 0x40 != 0 => (word(reader)? as i16).to_string(),
+                _ => byte(reader)?.to_string(),
+            };
+            let comparison = COMPARISONS
+                .get((condition & 7) as usize)
+                .copied()
+                .unwrap_or("?");
+            let target = word(reader)?;
+            format!("{variable} {comparison} {operand} -> {target:#06X}")
+        }
+        0x0B => format!("{}", word(reader)? >> 8),
+        0x0C => format!(
+            "ch[{}..={}] op={}",
+            byte(reader)?,
+            byte(reader)?,
+            byte(reader)?
+        ),
+        0x0D | 0x10 => format!("page={:#04X}", byte(reader)?),
+        0x0E => format!("page={:#04X} color={}", byte(reader)?, byte(reader)?),
+        0x0F => format!("{:#04X} -> {:#04X}", byte(reader)?, byte(reader)?),
+        0x12 => format!(
+            "id={:#05X} x={} y={} color={}",
+            word(reader)?,
+            byte(reader)?,
+            byte(reader)?,
+            byte(reader)?
+        ),
+        0x18 => format!(
+            "id={:#04X} freq={} vol={} channel={}",
+            word(reader)?,
+            byte(reader)?,
+            byte(reader)?,
+            byte(reader)?
+        ),
+        0x19 => format!("{:#06X}", word(reader)?),
+        0x1A => format!(
+            "id={:#04X} delay={} offset={}",
+            word(reader)?,
+            word(reader)?,
+            byte(reader)?
+        ),
+        _ => String::new(),
+    };
+    Ok((mnemonic, operands))
+}
+
+/// Loads `part` and disassembles its bytecode.
+pub fn run(
+    data_dir: &Path,
+    mem_list_file: &str,
+    part: GamePart,
+) -> Result<Vec<Instruction>, EngineError> {
+    let mut resource = ResourceRegistry::new(data_dir.to_path_buf(), mem_list_file);
+    resource.read_entries()?;
+    let loaded_part = resource.setup_part(part)?;
+    Ok(disassemble_part(&loaded_part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the x operand of a sprite opcode whose y is a variable and
+    /// whose zoom is the default, from offset 0x0010 and `x_bytes`.
+    fn sprite_x(x_mode: u8, x_bytes: &[u8]) -> Operand {
+        let mut bytes = vec![0x00, 0x08];
+        bytes.extend_from_slice(x_bytes);
+        bytes.push(0x42);
+        let mut reader = Cursor::new(bytes);
+        let sprite = SpriteOperands::read(0x40 | x_mode | 0x04, &mut reader).unwrap();
+        assert_eq!(sprite.offset, 0x0010);
+        assert!(matches!(sprite.y, Operand::Var(0x42)));
+        assert_eq!(reader.position(), reader.get_ref().len() as u64);
+        sprite.x
+    }
+
+    #[test]
+    fn sprite_x_encodings() {
+        assert!(matches!(sprite_x(0x00, &[0xFF, 0xF6]), Operand::Const(-10)));
+        assert!(matches!(sprite_x(0x10, &[0x23]), Operand::Var(0x23)));
+        assert!(matches!(sprite_x(0x20, &[0xC8]), Operand::Const(200)));
+        assert!(matches!(sprite_x(0x30, &[0x20]), Operand::Const(288)));
+    }
+}
@@ -1,25 +1,111 @@
-use std::path::PathBuf;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
 
+use clap::ValueEnum;
+use log::{info, warn};
+use rand::random;
+use serde::{Deserialize, Deserializer, de};
+use strum::IntoEnumIterator;
 use thiserror::Error;
-use winit::{event_loop::EventLoop, window::WindowBuilder};
+use winit::{
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::{Icon, Window, WindowBuilder, WindowLevel},
+};
 
 use crate::{
+    attract::AttractMode,
+    audio::MusicPack,
+    bank::{BankSource, DirectoryBankSource},
+    checkpoints,
+    clock::Clock,
+    color_vision::ColorVision,
+    compat::Quirks,
+    coverage::OpcodeCoverage,
+    debugger::Debugger,
     execution_context::ExecutionContext,
+    frame_dump::{FrameFormat, write_indexed_png, write_indexed_raw},
+    frame_limiter::FrameLimiter,
+    headless::HeadlessRenderer,
+    hud::{Hud, HudStats},
     loaded::{LoadedAsset, LoadedPart},
-    parts::GamePart,
-    renderer::{Renderer, SCALED_H, SCALED_W, SCREEN_H, SCREEN_W},
-    resource::{ResourceError, ResourceRegistry},
+    mem_report::MemoryUsage,
+    menu::{MENU_KEY, PartMenu},
+    parts::{GamePart, Segment},
+    presets::Preset,
+    recording::{InputPlayer, InputRecorder, RecordingError},
+    renderer::{
+        Aspect, Filter, NUM_COLORS, Renderer, RendererError, SCREEN_H, SCREEN_W,
+        SoftbufferRenderer, indexed_to_rgb,
+    },
+    resource::{DataSet, ResourceError, ResourceRegistry},
+    rewind::RewindBuffer,
+    snapshot::{Snapshot, SnapshotError},
     sys_event_handler::SysEventHandler,
-    video::Video,
-    vm::{Vm, VmError},
+    terminal::TerminalRenderer,
+    trace::{FrameTracer, TraceError},
+    video::{PageId, PaletteRequest, UNIFORM_FRAMES_WARNING, Video, VideoError},
+    vm::{FrameCounters, Vm, VmConfig, VmError},
 };
 
+pub use crate::attract::DEFAULT_ATTRACT_SECONDS;
+pub use crate::compat::Quirk;
+pub use crate::debugger::VariableAssignment;
+pub use crate::input::{InputSource, InputState};
+pub use crate::mem_entry::MemListFormat;
+pub use crate::resource::{DEFAULT_MEM_LIST_FILE, find_data_dir};
+pub use crate::rewind::DEFAULT_REWIND_FRAMES;
+pub use crate::text::Language;
+pub use crate::video::{BlendMode, InternalScale};
+pub use crate::vm::{DEFAULT_MAX_CALL_DEPTH, DEFAULT_WATCHDOG_LIMIT, ErrorPolicy, GameVersion};
+
+const WINDOW_TITLE: &str = "Another Rusty World";
+const WINDOW_ICON_PNG: &[u8] = include_bytes!("../assets/icon.png");
+const DEBUG_PALETTE_KEY: KeyCode = KeyCode::F4;
+const RELOAD_DATA_KEY: KeyCode = KeyCode::F5;
+const PAUSE_KEY: KeyCode = KeyCode::KeyP;
+const FRAME_STEP_KEY: KeyCode = KeyCode::KeyN;
+const SLOW_MOTION_KEY: KeyCode = KeyCode::F8;
+const DRAW_ORDER_KEY: KeyCode = KeyCode::F9;
+const DUMP_VM_KEY: KeyCode = KeyCode::F10;
+const REWIND_KEY: KeyCode = KeyCode::KeyB;
+const INSPECT_PIXEL_KEY: KeyCode = KeyCode::F11;
+const TOGGLE_PRESENT_KEY: KeyCode = KeyCode::KeyR;
+const FREEZE_VM_KEY: KeyCode = KeyCode::KeyV;
+const PALETTE_LOCK_KEY: KeyCode = KeyCode::KeyL;
+const BOUNDING_BOX_KEY: KeyCode = KeyCode::KeyO;
+const SLOW_MOTION_SPEED: f64 = 0.25;
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const EXPORT_PALETTE_KEY: KeyCode = KeyCode::F6;
+const IMPORT_PALETTE_KEY: KeyCode = KeyCode::F7;
+const IMPORTED_PALETTE_FILE: &str = "palette.act";
+const QUICK_SAVE_KEY: KeyCode = KeyCode::F12;
+const QUICK_LOAD_KEY: KeyCode = KeyCode::F1;
+const QUICK_SAVE_FILE: &str = "quicksave.arws";
+const MUTE_MUSIC_KEY: KeyCode = KeyCode::KeyM;
+const MUTE_SFX_KEY: KeyCode = KeyCode::KeyX;
+
 #[derive(Error, Debug)]
 pub enum EngineError {
     #[error("Resource registry error")]
-    ResourceError(ResourceError),
+    ResourceError(#[source] ResourceError),
     #[error("Unexpected error in VM execution")]
-    VmError(VmError),
+    VmError(#[source] VmError),
+    #[error("Input recording error")]
+    RecordingError(#[source] RecordingError),
+    #[error("Video error")]
+    VideoError(#[source] VideoError),
+    #[error("Frame trace error")]
+    TraceError(#[source] TraceError),
+    #[error("State snapshot error")]
+    SnapshotError(#[source] SnapshotError),
+    #[error("No checkpoint named {0}")]
+    UnknownCheckpoint(String),
 }
 
 impl From<ResourceError> for EngineError {
@@ -34,50 +120,853 @@ impl From<VmError> for EngineError {
     }
 }
 
-pub struct Engine {}
+impl From<VideoError> for EngineError {
+    fn from(value: VideoError) -> Self {
+        EngineError::VideoError(value)
+    }
+}
+
+impl From<SnapshotError> for EngineError {
+    fn from(value: SnapshotError) -> Self {
+        EngineError::SnapshotError(value)
+    }
+}
+
+impl From<TraceError> for EngineError {
+    fn from(value: TraceError) -> Self {
+        EngineError::TraceError(value)
+    }
+}
+
+impl From<RecordingError> for EngineError {
+    fn from(value: RecordingError) -> Self {
+        EngineError::RecordingError(value)
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RendererKind {
+    #[default]
+    Window,
+    Terminal,
+    Headless,
+}
+
+/// What a tick produced, for embedders that display frames themselves.
+///
+/// `indexed` holds the displayed page as 320x200 palette indices packed two
+/// pixels per byte, row-major with a stride of 160 bytes: the high nibble is
+/// the left pixel. `palette` maps those indices to `0x00RRGGBB`. `present`
+/// is false when the VM ran without requesting a blit, in which case the
+/// previous frame is still the one to show.
+pub struct FrameOutput {
+    pub indexed: Vec<u8>,
+    pub palette: [u32; NUM_COLORS],
+    pub present: bool,
+}
+
+impl FrameOutput {
+    /// The frame as one `0x00RRGGBB` value per pixel, rows top to bottom.
+    pub fn to_rgb(&self) -> Vec<u32> {
+        indexed_to_rgb(&self.indexed, &self.palette)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct WindowPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl FromStr for WindowPosition {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid window position {raw}, expected X,Y");
+        let (x, y) = raw.split_once(',').ok_or_else(invalid)?;
+        Ok(Self {
+            x: x.trim().parse().map_err(|_| invalid())?,
+            y: y.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowPosition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Clone)]
+pub struct EngineConfig {
+    pub data_dir: PathBuf,
+    pub mem_list: String,
+    pub mem_list_format: MemListFormat,
+    pub override_dir: Option<PathBuf>,
+    pub renderer: RendererKind,
+    pub record: Option<PathBuf>,
+    pub play: Option<PathBuf>,
+    pub trace_file: Option<PathBuf>,
+    pub preset: Option<Preset>,
+    pub part: Option<GamePart>,
+    pub variables: Vec<VariableAssignment>,
+    pub watchdog_limit: usize,
+    pub max_call_depth: usize,
+    pub skip_unknown_opcodes: bool,
+    pub mute: bool,
+    pub mute_music: bool,
+    pub mute_sfx: bool,
+    pub music_pack: Option<PathBuf>,
+    pub load_state: Option<PathBuf>,
+    pub preempt_budget: Option<usize>,
+    pub speed: f64,
+    pub pace_multiplier: f64,
+    pub max_fps: Option<u32>,
+    pub debug_console: bool,
+    pub error_policy: ErrorPolicy,
+    pub with_protection: bool,
+    pub game_version: GameVersion,
+    pub blend_mode: BlendMode,
+    pub internal_scale: InternalScale,
+    pub palette_fade: usize,
+    pub background_page: u8,
+    pub compat: Vec<Quirk>,
+    pub filter: Filter,
+    pub aspect: Aspect,
+    pub scale: u32,
+    pub window_pos: Option<WindowPosition>,
+    pub always_on_top: bool,
+    pub vsync: bool,
+    pub antialias: bool,
+    pub color_vision: ColorVision,
+    pub language: Language,
+    pub opcode_coverage: bool,
+    pub attract: Option<Duration>,
+    pub rewind_frames: usize,
+    pub skip_present: bool,
+    pub input_source: InputSource,
+}
+
+pub struct Engine {
+    vm: Vm,
+    context: ExecutionContext,
+    variable_overrides: Vec<(usize, i16)>,
+    reported_uniform_since: Option<usize>,
+    exported_palettes: usize,
+    vm_dumps: usize,
+    paused: bool,
+    frame_steps: usize,
+    speed: f64,
+    slow_motion: bool,
+    attract: Option<AttractMode>,
+    part_started: Option<Instant>,
+    rewind: RewindBuffer,
+    pending_snapshot: Option<Snapshot>,
+    inspect_pixels: bool,
+    present: bool,
+    vm_frozen: bool,
+}
 
 impl Engine {
-    pub fn run(data_dir: PathBuf) -> Result<(), EngineError> {
-        let event_loop = EventLoop::new().unwrap();
-        let window = WindowBuilder::new()
-            .with_title("Another Rusty World")
-            .with_inner_size(winit::dpi::PhysicalSize::new(
-                SCALED_W as u32,
-                SCALED_H as u32,
-            ))
-            .with_resizable(false)
-            .build(&event_loop)
-            .unwrap();
+    pub fn new(
+        config: &EngineConfig,
+        renderer: Box<dyn Renderer>,
+        seed: i16,
+    ) -> Result<Self, EngineError> {
+        let bank_source = Box::new(DirectoryBankSource::with_mem_list(
+            &config.data_dir,
+            &config.mem_list,
+        ));
+        Self::with_bank_source(config, renderer, seed, bank_source)
+    }
 
-        let mut _sys_event_handler = SysEventHandler::new(event_loop);
-        let mut resource = ResourceRegistry::new(data_dir);
-        let mut video = Video::new(Renderer::new(window));
-        let mut vm = Vm::default();
+    /// Like `new`, but reads the memlist and banks from `bank_source`
+    /// instead of `config.data_dir`, for frontends without a filesystem.
+    pub fn with_bank_source(
+        config: &EngineConfig,
+        renderer: Box<dyn Renderer>,
+        seed: i16,
+        bank_source: Box<dyn BankSource>,
+    ) -> Result<Self, EngineError> {
+        let default_part = if config.with_protection {
+            GamePart::One
+        } else {
+            GamePart::Two
+        };
+        let (preset_part, mut variable_overrides) = config
+            .preset
+            .map_or((default_part, Vec::new()), Preset::resolve);
+        let start_part = config.part.unwrap_or(preset_part);
+        variable_overrides.extend(
+            config
+                .variables
+                .iter()
+                .map(|assignment| (assignment.id, assignment.value)),
+        );
 
+        let mut resource = ResourceRegistry::with_bank_source(bank_source);
+        resource.set_mem_list_format(config.mem_list_format);
+        resource.set_override_dir(config.override_dir.clone());
         resource.read_entries()?;
+        let start_part = Self::select_start_part(&resource, start_part);
+        let mut frame_limiter = FrameLimiter::new(config.max_fps);
+        frame_limiter.set_pace(config.pace_multiplier);
+        let mut video = Video::new(renderer);
+        video.set_internal_scale(config.internal_scale);
+        video.set_blend_mode(config.blend_mode);
+        video.set_quirks(Quirks::new(&config.compat));
+        video.set_color_vision(config.color_vision);
+        video.set_palette_fade(config.palette_fade);
+        video.set_background_buffer(PageId::Numbered(config.background_page));
+        video.set_language(config.language);
+        video.set_present(!config.skip_present);
         let mut context = ExecutionContext::new(
             LoadedPart::default(),
             LoadedAsset::default(),
-            Some(GamePart::Two),
-            &mut resource,
-            &mut video,
+            start_part,
+            resource,
+            video,
+            frame_limiter,
         );
+        context
+            .audio
+            .set_music_pack(config.music_pack.clone().map(MusicPack::new));
+        context.set_speed(config.speed);
+        context.audio.set_music_muted(config.mute_music);
+        context.audio.set_sfx_muted(config.mute_sfx);
+
+        let mut vm = Vm::new(VmConfig::for_version(config.game_version, seed));
+        vm.set_error_policy(config.error_policy);
+        vm.set_watchdog_limit(config.watchdog_limit);
+        vm.set_max_call_depth(config.max_call_depth);
+        vm.set_preempt_budget(config.preempt_budget);
+        vm.set_skip_unknown_opcodes(config.skip_unknown_opcodes);
+        vm.set_protection_bypass(!config.with_protection);
+        if config.opcode_coverage {
+            vm.enable_coverage();
+        }
+
+        let mut engine = Self {
+            vm,
+            context,
+            variable_overrides,
+            reported_uniform_since: None,
+            exported_palettes: 0,
+            vm_dumps: 0,
+            paused: false,
+            frame_steps: 0,
+            speed: config.speed,
+            slow_motion: false,
+            attract: config
+                .attract
+                .map(|duration| AttractMode::new(duration, start_part)),
+            part_started: None,
+            rewind: RewindBuffer::new(config.rewind_frames),
+            pending_snapshot: None,
+            inspect_pixels: false,
+            present: !config.skip_present,
+            vm_frozen: false,
+        };
+        if let Some(path) = config.load_state.as_deref() {
+            engine.load_snapshot(path)?;
+        }
+        Ok(engine)
+    }
+
+    pub fn tick(&mut self, input: InputState) -> Result<(), EngineError> {
+        if self.vm_frozen {
+            self.context.video.redisplay()?;
+            return Ok(());
+        }
+        self.vm.apply_input(input);
+        self.update_part()?;
+        self.vm.check_channel_requests()?;
+        self.vm.host_frame(&mut self.context)?;
+        self.check_uniform_frames();
+        Ok(())
+    }
+
+    /// Warns once when the screen has stayed a single color for a long
+    /// time, to make "black screen" reports point at a part and frame.
+    fn check_uniform_frames(&mut self) {
+        let video = &self.context.video;
+        if let Some(since) = video.uniform_since()
+            && video.frames_presented() - since >= UNIFORM_FRAMES_WARNING
+            && self.reported_uniform_since != Some(since)
+        {
+            self.reported_uniform_since = Some(since);
+            warn!(
+                "Screen has shown a single color since frame {since} in {}",
+                self.current_part()
+            );
+        }
+    }
+
+    /// Like `tick`, but returns the frame to display.
+    pub fn tick_frame(&mut self, input: InputState) -> Result<FrameOutput, EngineError> {
+        let presented = self.frames_presented();
+        self.tick(input)?;
+        let video = &self.context.video;
+        Ok(FrameOutput {
+            indexed: video.front_page().into_owned(),
+            palette: video.palette(),
+            present: self.frames_presented() != presented,
+        })
+    }
+
+    pub fn run(config: EngineConfig) -> Result<(), EngineError> {
+        let (renderer, mut sys_event_handler): (Box<dyn Renderer>, Option<SysEventHandler>) =
+            match config.renderer {
+                RendererKind::Window => {
+                    let event_loop = EventLoop::new().unwrap();
+                    let window = Self::build_window(&event_loop, &config);
+                    let window_size = window.inner_size();
+                    (
+                        Box::new(SoftbufferRenderer::new(
+                            window,
+                            config.filter,
+                            config.aspect,
+                            config.vsync,
+                            config.antialias,
+                        )),
+                        Some(SysEventHandler::new(
+                            event_loop,
+                            window_size,
+                            config.input_source,
+                        )),
+                    )
+                }
+                RendererKind::Terminal => (Box::new(TerminalRenderer::new()), None),
+                RendererKind::Headless => (Box::new(HeadlessRenderer::default()), None),
+            };
+
+        let mut player = config.play.as_deref().map(InputPlayer::open).transpose()?;
+        let seed = player
+            .as_ref()
+            .map_or_else(random::<i16>, InputPlayer::seed);
+        let mut recorder = config
+            .record
+            .as_deref()
+            .map(|path| InputRecorder::create(path, seed))
+            .transpose()?;
+        let mut tracer = config
+            .trace_file
+            .as_deref()
+            .map(FrameTracer::create)
+            .transpose()?;
+        let mut debugger = Debugger::new(config.debug_console);
+        let mut hud = Hud::new();
+        let mut menu = PartMenu::default();
+        let mut title = String::from(WINDOW_TITLE);
+        let mut engine = Self::new(&config, renderer, seed)?;
+        if !config.mute {
+            engine.context.audio.open_output();
+        }
 
+        // Events are pumped right before the input is sampled so that a key
+        // pressed between two frames reaches the very next `tick`, which runs
+        // input -> update_part -> check_channel_requests -> host_frame.
         loop {
-            Self::update_part(&mut context, &mut vm)?;
-            vm.check_channel_requests()?;
-            vm.host_frame(&mut context)?;
-            _sys_event_handler.pump_events();
+            if let Some(sys_event_handler) = sys_event_handler.as_mut() {
+                sys_event_handler.pump_events();
+                for key_code in sys_event_handler.take_key_presses() {
+                    if menu.is_open() {
+                        if let Some(part) = menu.handle_key(key_code) {
+                            engine.switch_part(part);
+                        }
+                        continue;
+                    }
+                    if key_code == MENU_KEY {
+                        menu.open(engine.available_parts(), engine.current_part());
+                        continue;
+                    }
+                    debugger.handle_key(key_code);
+                    hud.handle_key(key_code);
+                    engine.handle_key(key_code);
+                }
+                if sys_event_handler.quit_requested() {
+                    break;
+                }
+            }
+            debugger.process_console(&mut engine.vm);
+            let overlay = if menu.is_open() {
+                menu.overlay()
+            } else {
+                let mut overlay = hud.overlay(&engine.stats());
+                overlay.extend(debugger.overlay(&engine.vm));
+                overlay
+            };
+            engine.context.video.set_overlay(overlay);
+
+            if menu.is_open() || (engine.paused && engine.frame_steps == 0) {
+                engine.context.video.redisplay()?;
+                thread::sleep(PAUSE_POLL_INTERVAL);
+            } else {
+                let live_input = sys_event_handler
+                    .as_mut()
+                    .map_or_else(InputState::default, SysEventHandler::take_input);
+                let input = if engine.attract.is_some() {
+                    InputState::default()
+                } else {
+                    Self::next_input(&mut player, live_input)
+                };
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(input)?;
+                }
+                engine.update_attract();
+                engine.capture_rewind_frame();
+                let presented = engine.frames_presented();
+                let result = engine.tick(input);
+                if let Some(tracer) = tracer.as_mut() {
+                    let error = result.as_ref().err().map(|e| e as &dyn std::error::Error);
+                    tracer.record(engine.current_part(), engine.frame_counters(), error)?;
+                }
+                result?;
+                if engine.paused && engine.frames_presented() != presented {
+                    engine.frame_steps -= 1;
+                }
+                engine.context.frame_limiter.wait();
+                if engine.attract.is_none() && engine.has_ended() {
+                    info!("Game ended");
+                    break;
+                }
+            }
+            hud.record_frame();
+            let mut status = format!(
+                "{WINDOW_TITLE} - {} - {:.0} FPS",
+                engine.current_part(),
+                hud.fps()
+            );
+            if engine.inspect_pixels
+                && let Some((x, y)) = sys_event_handler
+                    .as_ref()
+                    .and_then(|handler| handler.cursor_on_screen(config.aspect))
+            {
+                let (index, rgb) = engine.context.video.pixel_at(x, y);
+                status.push_str(&format!(" - ({x}, {y}) color {index:#X} #{rgb:06X}"));
+            }
+            if status != title {
+                engine.context.video.set_title(&status);
+                title = status;
+            }
+        }
+
+        if let Some(recorder) = recorder {
+            recorder.finish()?;
+        }
+        if let Some(coverage) = engine.opcode_coverage() {
+            println!("{coverage}");
+        }
+        Ok(())
+    }
+
+    pub fn frame_counters(&self) -> FrameCounters {
+        self.vm.frame_counters()
+    }
+
+    pub fn opcode_coverage(&self) -> Option<&OpcodeCoverage> {
+        self.vm.coverage()
+    }
+
+    fn update_attract(&mut self) {
+        let Some(attract) = self.attract.as_mut() else {
+            return;
+        };
+        let resource = &self.context.resource;
+        let halted = self.context.part_to_load.is_none() && self.vm.is_halted();
+        if let Some(next_part) = attract.next_part(self.context.current_part, halted, |part| {
+            resource.is_part_available(part)
+        }) {
+            info!("Attract mode switching to {next_part}");
+            self.context.part_to_load = Some(next_part);
+        }
+    }
+
+    fn stats(&self) -> HudStats {
+        HudStats {
+            part: self.current_part(),
+            ready_channels: self.vm.ready_channels(),
+            resource: self.context.loaded_asset.last_loaded,
+            counters: self.vm.frame_counters(),
+        }
+    }
+
+    fn handle_key(&mut self, key_code: KeyCode) {
+        match key_code {
+            DEBUG_PALETTE_KEY => self.context.video.toggle_debug_palette(),
+            RELOAD_DATA_KEY => self.reload_data(),
+            PAUSE_KEY => self.toggle_pause(),
+            FRAME_STEP_KEY => self.step_frame(),
+            SLOW_MOTION_KEY => self.toggle_slow_motion(),
+            DRAW_ORDER_KEY => self.context.video.toggle_draw_order(),
+            EXPORT_PALETTE_KEY => self.export_palette(),
+            IMPORT_PALETTE_KEY => self.import_palette(),
+            DUMP_VM_KEY => self.dump_vm(),
+            REWIND_KEY => self.rewind(),
+            INSPECT_PIXEL_KEY => self.toggle_pixel_inspector(),
+            TOGGLE_PRESENT_KEY => self.toggle_present(),
+            FREEZE_VM_KEY => self.toggle_vm_frozen(),
+            PALETTE_LOCK_KEY => self.toggle_palette_lock(),
+            BOUNDING_BOX_KEY => self.toggle_bounding_boxes(),
+            QUICK_SAVE_KEY => self.quick_save(),
+            QUICK_LOAD_KEY => self.quick_load(),
+            MUTE_MUSIC_KEY => self.toggle_music_mute(),
+            MUTE_SFX_KEY => self.toggle_sfx_mute(),
+            _ => {}
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.frame_steps = 0;
+        info!("{}", if self.paused { "Paused" } else { "Resumed" });
+    }
+
+    /// Pauses the engine if needed and lets it run until the next blit, so
+    /// each press advances exactly one displayed frame.
+    fn step_frame(&mut self) {
+        self.paused = true;
+        self.frame_steps += 1;
+    }
+
+    /// Keeps the VM running without presenting anything, to measure its
+    /// cost apart from the renderer's.
+    fn toggle_present(&mut self) {
+        self.present = !self.present;
+        self.context.video.set_present(self.present);
+        info!(
+            "Presenting {}",
+            if self.present { "enabled" } else { "disabled" }
+        );
+    }
+
+    /// Stops running frames while still presenting the last one, the
+    /// counterpart of `toggle_present`.
+    fn toggle_vm_frozen(&mut self) {
+        self.vm_frozen = !self.vm_frozen;
+        info!("VM {}", if self.vm_frozen { "frozen" } else { "resumed" });
+    }
+
+    fn toggle_bounding_boxes(&mut self) {
+        let enabled = self.context.video.toggle_bounding_boxes();
+        info!(
+            "Polygon bounding boxes {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    fn toggle_music_mute(&mut self) {
+        let muted = self.context.audio.toggle_music_muted();
+        info!("Music {}", if muted { "muted" } else { "unmuted" });
+    }
+
+    fn toggle_sfx_mute(&mut self) {
+        let muted = self.context.audio.toggle_sfx_muted();
+        info!("Sound effects {}", if muted { "muted" } else { "unmuted" });
+    }
+
+    fn toggle_palette_lock(&mut self) {
+        let locked = self.context.video.toggle_palette_lock();
+        info!("Palette {}", if locked { "locked" } else { "unlocked" });
+    }
+
+    fn toggle_pixel_inspector(&mut self) {
+        self.inspect_pixels = !self.inspect_pixels;
+        info!(
+            "Pixel inspector {}",
+            if self.inspect_pixels {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+
+    fn capture_rewind_frame(&mut self) {
+        self.rewind.push(
+            self.context.current_part,
+            self.vm.save_state(),
+            self.context.video.save_state(),
+        );
+    }
+
+    /// Pauses the engine and goes back one frame, dropping it from the
+    /// buffer so the next press goes further back.
+    fn rewind(&mut self) {
+        self.paused = true;
+        self.frame_steps = 0;
+        let Some((vm_state, video_state)) = self.rewind.pop() else {
+            info!("Nothing left to rewind");
+            return;
+        };
+        self.vm.load_state(&vm_state);
+        self.context.video.load_state(&video_state);
+        info!("Rewound one frame, {} left", self.rewind.len());
+    }
+
+    fn quick_save(&mut self) {
+        match self.save_snapshot(Path::new(QUICK_SAVE_FILE)) {
+            Ok(()) => info!("State saved to {QUICK_SAVE_FILE}"),
+            Err(e) => warn!("Unable to save state: {e}"),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        match self.load_snapshot(Path::new(QUICK_SAVE_FILE)) {
+            Ok(()) => info!("State loaded from {QUICK_SAVE_FILE}"),
+            Err(e) => warn!("Unable to load state: {e}"),
+        }
+    }
+
+    fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+        let speed = if self.slow_motion {
+            SLOW_MOTION_SPEED
+        } else {
+            self.speed
+        };
+        self.context.set_speed(speed);
+        info!("Playback speed set to {speed}x");
+    }
+
+    fn reload_data(&mut self) {
+        match self.context.resource.read_entries() {
+            Ok(()) => {
+                self.context.loaded_asset = LoadedAsset::default();
+                info!("Data files reloaded from disk");
+            }
+            Err(e) => warn!("Unable to reload data files: {e}"),
+        }
+    }
+
+    fn export_palette(&mut self) {
+        let path = format!("palette-{:03}.act", self.exported_palettes);
+        match fs::write(&path, self.context.video.export_palette()) {
+            Ok(()) => {
+                info!("Palette exported to {path}");
+                self.exported_palettes += 1;
+            }
+            Err(e) => warn!("Unable to export palette to {path}: {e}"),
+        }
+    }
+
+    fn dump_vm(&mut self) {
+        let path = format!("vm-dump-{:03}.json", self.vm_dumps);
+        let result = serde_json::to_string_pretty(&self.vm.dump_debug())
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => {
+                info!("VM state dumped to {path}");
+                self.vm_dumps += 1;
+            }
+            Err(e) => warn!("Unable to dump VM state to {path}: {e}"),
+        }
+    }
+
+    fn import_palette(&mut self) {
+        let result = fs::read(IMPORTED_PALETTE_FILE)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                self.context
+                    .video
+                    .import_palette(&data)
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => info!("Palette imported from {IMPORTED_PALETTE_FILE}"),
+            Err(e) => warn!("Unable to import palette from {IMPORTED_PALETTE_FILE}: {e}"),
+        }
+    }
+
+    /// The game is over once the final part has halted on its own: every
+    /// channel is dead or paused with nothing left to wake them up, no part
+    /// switch is pending and no channel was killed after an error.
+    pub fn has_ended(&self) -> bool {
+        self.context.current_part.is_final()
+            && self.context.part_to_load.is_none()
+            && self.vm.is_halted()
+            && !self.vm.has_failed_channels()
+    }
+
+    pub fn current_part(&self) -> GamePart {
+        self.context.current_part
+    }
+
+    pub fn loaded_assets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.context.loaded_asset.indices()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            segments: self.context.loaded_part.size(),
+            assets: self.context.loaded_asset.size(),
+        }
+    }
+
+    pub fn eager_memory_usage(&self) -> usize {
+        self.context.resource.total_unpacked_size()
+    }
+
+    pub fn frames_presented(&self) -> usize {
+        self.context.video.frames_presented()
+    }
+
+    /// Writes the page currently on screen, e.g. to compare a headless run
+    /// against a golden image.
+    pub fn save_front_page(&self, path: &Path, format: FrameFormat) -> Result<(), RendererError> {
+        let video = &self.context.video;
+        let (page, palette) = (video.front_page(), video.palette());
+        match format {
+            FrameFormat::Png => write_indexed_png(path, &page, &palette),
+            FrameFormat::Raw => write_indexed_raw(path, &page, &palette),
+        }
+    }
+
+    /// Replaces the time source used to pace frames and blits.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.context.frame_limiter.set_clock(clock);
+    }
+
+    pub fn set_uncapped(&mut self, uncapped: bool) {
+        self.context.set_uncapped(uncapped);
+    }
+
+    /// Parts the data set holds, in game order.
+    pub fn available_parts(&self) -> Vec<GamePart> {
+        GamePart::iter()
+            .filter(|&part| self.context.resource.is_part_available(part))
+            .collect()
+    }
+
+    /// Restarts the game at the start of `part` from the next frame on.
+    pub fn switch_part(&mut self, part: GamePart) {
+        info!("Switching to {part}");
+        self.variable_overrides.clear();
+        self.context.part_to_load = Some(part);
+    }
+
+    /// Writes the state the engine is in between two frames, see `Snapshot`
+    /// for the format.
+    pub fn save_snapshot(&self, path: &Path) -> Result<(), EngineError> {
+        let snapshot = Snapshot {
+            part: self.context.current_part,
+            assets: self.context.loaded_asset.indices().collect(),
+            vm: self.vm.save_state(),
+            video: self.context.video.save_state(),
+        };
+        Ok(snapshot.save(path)?)
+    }
+
+    /// Reads a snapshot and resumes from it on the next tick. The part is
+    /// always set up again so the channels point into fresh bytecode, even
+    /// when it is the one already running.
+    pub fn load_snapshot(&mut self, path: &Path) -> Result<(), EngineError> {
+        let snapshot = Snapshot::load(path, self.context.video.page_size())?;
+        self.variable_overrides.clear();
+        self.context.part_to_load = Some(snapshot.part);
+        self.pending_snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    /// Restarts the game at the named entry of `checkpoints::CHECKPOINTS`
+    /// from the next frame on.
+    pub fn jump_to_checkpoint(&mut self, name: &str) -> Result<(), EngineError> {
+        let checkpoint = checkpoints::find(name)
+            .ok_or_else(|| EngineError::UnknownCheckpoint(name.to_string()))?;
+        let (part, variable_overrides) = Preset::Checkpoint(checkpoint).resolve();
+        info!("Jumping to checkpoint {} in {part}", checkpoint.name);
+        self.variable_overrides = variable_overrides;
+        self.context.part_to_load = Some(part);
+        Ok(())
+    }
+
+    fn select_start_part(resource: &ResourceRegistry, requested: GamePart) -> GamePart {
+        if resource.data_set() == DataSet::Demo {
+            warn!(
+                "Memlist holds only {} entries, this looks like a demo data set",
+                resource.mem_list.len()
+            );
+        }
+        if resource.is_part_available(requested) {
+            return requested;
+        }
+        match GamePart::iter().find(|&part| resource.is_part_available(part)) {
+            Some(fallback) => {
+                warn!("{requested} is not available in this data set, starting at {fallback}");
+                fallback
+            }
+            None => requested,
+        }
+    }
+
+    fn next_input(player: &mut Option<InputPlayer>, live_input: InputState) -> InputState {
+        let Some(recorded) = player.as_mut() else {
+            return live_input;
+        };
+        match recorded.next_input() {
+            Some(input) => input,
+            None => {
+                info!("Input playback finished, switching to live input");
+                *player = None;
+                live_input
+            }
+        }
+    }
+
+    fn build_window(event_loop: &EventLoop<()>, config: &EngineConfig) -> Window {
+        let (width, height) = config.aspect.window_size(config.scale);
+        let mut builder = WindowBuilder::new()
+            .with_title(WINDOW_TITLE)
+            .with_inner_size(winit::dpi::PhysicalSize::new(width, height))
+            .with_resizable(false)
+            .with_window_icon(Self::window_icon());
+        #[cfg(target_os = "linux")]
+        {
+            use winit::platform::wayland::WindowBuilderExtWayland;
+            builder = builder.with_name(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_NAME"));
+        }
+        if let Some(WindowPosition { x, y }) = config.window_pos {
+            builder = builder.with_position(winit::dpi::PhysicalPosition::new(x, y));
         }
+        if config.always_on_top {
+            builder = builder.with_window_level(WindowLevel::AlwaysOnTop);
+        }
+        builder.build(event_loop).unwrap()
     }
 
-    fn update_part(context: &mut ExecutionContext, vm: &mut Vm) -> Result<(), EngineError> {
+    fn window_icon() -> Option<Icon> {
+        let decode = || -> Result<Icon, String> {
+            let mut reader = png::Decoder::new(WINDOW_ICON_PNG)
+                .read_info()
+                .map_err(|e| e.to_string())?;
+            let mut rgba = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut rgba).map_err(|e| e.to_string())?;
+            if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+                return Err(format!("expected 8-bit RGBA, got {:?}", info.color_type));
+            }
+            rgba.truncate(info.buffer_size());
+            Icon::from_rgba(rgba, info.width, info.height).map_err(|e| e.to_string())
+        };
+        decode()
+            .inspect_err(|e| warn!("Unable to load the window icon: {e}"))
+            .ok()
+    }
+
+    fn update_part(&mut self) -> Result<(), EngineError> {
+        let context = &mut self.context;
         if let Some(part_id) = context.part_to_load {
-            vm.init_part()?;
+            self.vm.init_part()?;
+            context.audio.stop_all();
+            for (id, value) in self.variable_overrides.drain(..) {
+                self.vm.set_variable(id, value);
+            }
 
             let loaded_part = context.resource.setup_part(part_id)?;
-            if let Some(polygon) = &loaded_part.polygon {
-                let data = polygon.get_ref();
+            if let Some(data) = loaded_part.segment(Segment::Polygon) {
                 let is_bg_data = data.len() == SCREEN_W * SCREEN_H / 2;
                 if is_bg_data {
                     context.video.copy_bg(data);
@@ -85,8 +974,233 @@ impl Engine {
             }
             context.loaded_part = loaded_part;
             context.loaded_asset = LoadedAsset::default();
+            context.current_part = part_id;
             context.part_to_load = None;
+            if let Some(snapshot) = self.pending_snapshot.take() {
+                for index in snapshot.assets {
+                    let asset = context.resource.load_entry(index)?;
+                    context.loaded_asset.assets.insert(index, asset);
+                }
+                self.vm.load_state(&snapshot.vm);
+                context.video.load_state(&snapshot.video);
+                if let Some(palette_id) = snapshot.video.palette_id {
+                    context
+                        .video
+                        .request_palette(PaletteRequest::Change(palette_id));
+                }
+            }
+            if let Some(next_part) = part_id.successor() {
+                context.resource.prefetch_part(next_part);
+            }
+            self.log_part_transition(part_id);
         }
         Ok(())
     }
+
+    /// Logs each part as it gets installed with the time spent in the one
+    /// before, which gives a timeline of the playthrough.
+    fn log_part_transition(&mut self, part_id: GamePart) {
+        let now = Instant::now();
+        let previous = self.part_started.replace(now);
+        let [palette, bytecode, cinematic, polygon] = [
+            Segment::Palette,
+            Segment::Bytecode,
+            Segment::PolyCinematic,
+            Segment::Polygon,
+        ]
+        .map(|segment| {
+            self.context
+                .loaded_part
+                .segment(segment)
+                .map_or(0, <[u8]>::len)
+        });
+        let elapsed = previous.map_or_else(
+            || String::from("at startup"),
+            |started| format!("after {:.1?}", now - started),
+        );
+        info!(
+            "Entered part {part_id:?} ({:#06X}) {elapsed}, segments: palette {palette} B, \
+             bytecode {bytecode} B, cinematic {cinematic} B, polygon {polygon} B",
+            part_id as u16
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::{
+        channel::{Channel, ProcessCounter, State},
+        test_data::{TracingBankSource, bank_source},
+        vm::VmState,
+    };
+
+    fn config(part: GamePart) -> EngineConfig {
+        EngineConfig {
+            data_dir: PathBuf::new(),
+            mem_list: String::new(),
+            mem_list_format: Default::default(),
+            override_dir: None,
+            renderer: RendererKind::Headless,
+            record: None,
+            play: None,
+            trace_file: None,
+            preset: None,
+            part: Some(part),
+            variables: Vec::new(),
+            watchdog_limit: crate::vm::DEFAULT_WATCHDOG_LIMIT,
+            max_call_depth: crate::vm::DEFAULT_MAX_CALL_DEPTH,
+            skip_unknown_opcodes: false,
+            mute: true,
+            mute_music: false,
+            mute_sfx: false,
+            music_pack: None,
+            load_state: None,
+            preempt_budget: None,
+            speed: 1.0,
+            pace_multiplier: 1.0,
+            max_fps: None,
+            debug_console: false,
+            error_policy: Default::default(),
+            with_protection: false,
+            game_version: Default::default(),
+            blend_mode: Default::default(),
+            internal_scale: Default::default(),
+            palette_fade: 0,
+            background_page: 0,
+            compat: Vec::new(),
+            filter: Default::default(),
+            aspect: Default::default(),
+            scale: 1,
+            window_pos: None,
+            always_on_top: false,
+            vsync: false,
+            antialias: false,
+            color_vision: Default::default(),
+            language: Default::default(),
+            opcode_coverage: false,
+            attract: None,
+            rewind_frames: 0,
+            skip_present: true,
+            input_source: Default::default(),
+        }
+    }
+
+    /// Palette, bytecode and cinematic of parts Two and Five, plus the
+    /// polygons Five shares with other parts. Each bytecode yields once
+    /// per frame.
+    fn part_entries() -> Vec<(usize, Vec<u8>)> {
+        vec![
+            (0x17, Vec::new()),
+            (0x18, vec![0x06, 0x07, 0x00, 0x00]),
+            (0x19, Vec::new()),
+            (0x20, Vec::new()),
+            (0x21, vec![0x06, 0x06, 0x07, 0x00, 0x00]),
+            (0x22, Vec::new()),
+            (0x11, Vec::new()),
+        ]
+    }
+
+    fn engine(part: GamePart, source: Box<dyn BankSource>) -> Engine {
+        engine_with(&config(part), source)
+    }
+
+    fn engine_with(config: &EngineConfig, source: Box<dyn BankSource>) -> Engine {
+        let renderer = Box::new(HeadlessRenderer::default());
+        let mut engine = Engine::with_bank_source(config, renderer, 0, source).unwrap();
+        engine.set_uncapped(true);
+        engine
+    }
+
+    /// Runs one frame of `part` with `bytecode` and tells whether the game
+    /// ended.
+    fn ends_after_frame(part: GamePart, bytecode: &[u8], error_policy: ErrorPolicy) -> bool {
+        let [palette, code, cinematic, polygon] = ResourceRegistry::segment_indices(part);
+        let entries = [
+            (palette, Vec::new()),
+            (code, bytecode.to_vec()),
+            (cinematic, Vec::new()),
+            (polygon, Vec::new()),
+        ];
+        let config = EngineConfig {
+            error_policy,
+            ..config(part)
+        };
+        let mut engine = engine_with(&config, Box::new(bank_source(&entries)));
+        engine.tick(InputState::default()).unwrap();
+        engine.has_ended()
+    }
+
+    #[test]
+    fn snapshot_of_another_part_sets_it_up_before_the_channels() {
+        let mut engine = engine(GamePart::Two, Box::new(bank_source(&part_entries())));
+        engine.tick(InputState::default()).unwrap();
+        assert_eq!(engine.current_part(), GamePart::Two);
+
+        let mut vm = VmState::default();
+        vm.channels[3] = Channel {
+            state: State::Running,
+            pc: ProcessCounter::Valid(1),
+            next_pc: None,
+        };
+        vm.running_channel_id = 3;
+        let path = env::temp_dir().join(format!("arw-engine-{}.arws", std::process::id()));
+        Snapshot {
+            part: GamePart::Five,
+            assets: Vec::new(),
+            vm,
+            video: engine.context.video.save_state(),
+        }
+        .save(&path)
+        .unwrap();
+        let loaded = engine.load_snapshot(&path);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        engine.tick(InputState::default()).unwrap();
+
+        assert_eq!(engine.current_part(), GamePart::Five);
+        let channels = engine.vm.dump_debug().channels;
+        assert_eq!(channels[0].pc, None);
+        // Channel 3 resumed from offset 1 in part Five's bytecode and
+        // yielded again right after.
+        assert_eq!(channels[3].pc, Some(2));
+    }
+
+    #[test]
+    fn starting_part_loads_only_its_own_segments() {
+        let (source, reads) = TracingBankSource::new(bank_source(&part_entries()));
+        let mut engine = engine(GamePart::Five, Box::new(source));
+        engine.tick(InputState::default()).unwrap();
+
+        assert_eq!(engine.current_part(), GamePart::Five);
+        let mut reads = reads.borrow().clone();
+        reads.sort_unstable();
+        assert_eq!(reads, [0x11, 0x20, 0x21, 0x22]);
+    }
+
+    #[test]
+    fn game_ends_when_the_final_part_halts() {
+        assert!(ends_after_frame(
+            GamePart::Eigth,
+            &[0x11],
+            ErrorPolicy::Abort
+        ));
+        assert!(!ends_after_frame(
+            GamePart::Two,
+            &[0x11],
+            ErrorPolicy::Abort
+        ));
+    }
+
+    #[test]
+    fn failed_channels_do_not_end_the_game() {
+        // 0x30 is not an opcode, so channel 0 is killed after the error.
+        assert!(!ends_after_frame(
+            GamePart::Eigth,
+            &[0x30],
+            ErrorPolicy::SkipChannel
+        ));
+    }
 }
@@ -1,36 +1,74 @@
-use std::time::Instant;
-
 use crate::{
+    audio::Mixer,
+    bank::MemoryBankSource,
+    frame_limiter::FrameLimiter,
+    headless::HeadlessRenderer,
     loaded::{LoadedAsset, LoadedPart},
     parts::GamePart,
     resource::ResourceRegistry,
     video::Video,
 };
 
-pub struct ExecutionContext<'a> {
+pub struct ExecutionContext {
     pub loaded_part: LoadedPart,
     pub loaded_asset: LoadedAsset,
     pub part_to_load: Option<GamePart>,
-    pub resource: &'a mut ResourceRegistry,
-    pub video: &'a mut Video,
-    pub last_rendering: Instant,
+    pub current_part: GamePart,
+    pub resource: ResourceRegistry,
+    pub video: Video,
+    pub frame_limiter: FrameLimiter,
+    pub audio: Mixer,
 }
 
-impl<'a> ExecutionContext<'a> {
+impl ExecutionContext {
     pub fn new(
         loaded_part: LoadedPart,
         loaded_asset: LoadedAsset,
-        part_to_load: Option<GamePart>,
-        resource: &'a mut ResourceRegistry,
-        video: &'a mut Video,
+        start_part: GamePart,
+        resource: ResourceRegistry,
+        video: Video,
+        frame_limiter: FrameLimiter,
     ) -> Self {
         Self {
             loaded_part,
             loaded_asset,
-            part_to_load,
+            part_to_load: Some(start_part),
+            current_part: start_part,
             resource,
             video,
-            last_rendering: Instant::now(),
+            frame_limiter,
+            audio: Mixer::new(),
         }
     }
+
+    /// A context with no data files and no window, running `loaded_part` on
+    /// an empty in-memory bank source, for driving opcodes directly.
+    pub fn headless(loaded_part: LoadedPart) -> Self {
+        let resource = ResourceRegistry::with_bank_source(Box::<MemoryBankSource>::default());
+        let mut context = Self::new(
+            loaded_part,
+            LoadedAsset::default(),
+            GamePart::One,
+            resource,
+            Video::new(Box::new(HeadlessRenderer::default())),
+            FrameLimiter::new(None),
+        );
+        context.part_to_load = None;
+        context.set_uncapped(true);
+        context
+    }
+
+    /// Scales the frame delays by `speed` and mutes the audio below 1x,
+    /// since the mixer always plays at the original rate.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.frame_limiter.set_speed(speed);
+        self.audio.set_slowed(speed < 1.0);
+    }
+
+    /// Drops every frame sleep so each blit is an instantaneous frame
+    /// boundary; progress can then be measured with
+    /// `Video::frames_presented`.
+    pub fn set_uncapped(&mut self, uncapped: bool) {
+        self.frame_limiter.set_uncapped(uncapped);
+    }
 }
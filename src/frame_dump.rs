@@ -0,0 +1,177 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use png::{BitDepth, ColorType, Encoder};
+use rand::random;
+use thiserror::Error;
+
+use crate::{
+    engine::{Engine, EngineConfig, EngineError},
+    input::InputState,
+    renderer::{
+        NUM_COLORS, Renderer, RendererError, SCREEN_H, SCREEN_W, act_to_palette, palette_to_act,
+        read_palette,
+    },
+};
+
+const RAW_MAGIC: &[u8; 4] = b"ARWF";
+const RAW_VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum FrameDumpError {
+    #[error("Unable to create the output directory")]
+    Io(#[source] io::Error),
+    #[error("Engine error while dumping frames")]
+    EngineError(#[source] EngineError),
+}
+
+impl From<io::Error> for FrameDumpError {
+    fn from(value: io::Error) -> Self {
+        FrameDumpError::Io(value)
+    }
+}
+
+impl From<EngineError> for FrameDumpError {
+    fn from(value: EngineError) -> Self {
+        FrameDumpError::EngineError(value)
+    }
+}
+
+/// Writes a page as a 4-bit indexed PNG through `palette`. Video pages
+/// already pack two pixels per byte, high nibble first, which is the PNG row
+/// layout for that bit depth.
+pub fn write_indexed_png(
+    path: &Path,
+    src: &[u8],
+    palette: &[u32; NUM_COLORS],
+) -> Result<(), RendererError> {
+    let mut encoder = Encoder::new(
+        BufWriter::new(File::create(path)?),
+        SCREEN_W as u32,
+        SCREEN_H as u32,
+    );
+    encoder.set_color(ColorType::Indexed);
+    encoder.set_depth(BitDepth::Four);
+    encoder.set_palette(palette_to_act(palette).to_vec());
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(src)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes a page and its palette without converting to RGB:
+///
+/// | offset | size  | content                                          |
+/// |--------|-------|--------------------------------------------------|
+/// | 0      | 4     | magic `ARWF`                                     |
+/// | 4      | 1     | format version, 1                                |
+/// | 5      | 2     | width in pixels, big endian, 320                 |
+/// | 7      | 2     | height in pixels, big endian, 200                |
+/// | 9      | 48    | 16 palette entries as R, G, B bytes              |
+/// | 57     | 32000 | pixel indices, two per byte, high nibble first,  |
+/// |        |       | rows top to bottom                               |
+pub fn write_indexed_raw(
+    path: &Path,
+    src: &[u8],
+    palette: &[u32; NUM_COLORS],
+) -> Result<(), RendererError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(RAW_MAGIC)?;
+    writer.write_u8(RAW_VERSION)?;
+    writer.write_u16::<BigEndian>(SCREEN_W as u16)?;
+    writer.write_u16::<BigEndian>(SCREEN_H as u16)?;
+    writer.write_all(&palette_to_act(palette))?;
+    writer.write_all(src)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameFormat {
+    Png,
+    /// See `write_indexed_raw`.
+    Raw,
+}
+
+impl FrameFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Raw => "raw",
+        }
+    }
+}
+
+/// Writes every presented frame as a PNG through the current palette, or as
+/// raw indexed data.
+pub struct FrameDumpRenderer {
+    palette: [u32; NUM_COLORS],
+    output_dir: PathBuf,
+    format: FrameFormat,
+    next_frame: usize,
+}
+
+impl FrameDumpRenderer {
+    pub fn new(output_dir: &Path, format: FrameFormat) -> Self {
+        Self {
+            palette: Default::default(),
+            output_dir: output_dir.to_path_buf(),
+            format,
+            next_frame: 0,
+        }
+    }
+}
+
+impl Renderer for FrameDumpRenderer {
+    fn set_palette(&mut self, reader: &mut dyn Read) -> Result<(), RendererError> {
+        self.palette = read_palette(reader)?;
+        Ok(())
+    }
+
+    fn update_display(&mut self, src: &[u8]) -> Result<(), RendererError> {
+        let path = self.output_dir.join(format!(
+            "frame-{:06}.{}",
+            self.next_frame,
+            self.format.extension()
+        ));
+        match self.format {
+            FrameFormat::Png => write_indexed_png(&path, src, &self.palette)?,
+            FrameFormat::Raw => write_indexed_raw(&path, src, &self.palette)?,
+        }
+        self.next_frame += 1;
+        Ok(())
+    }
+
+    fn palette(&self) -> [u32; NUM_COLORS] {
+        self.palette
+    }
+
+    fn import_palette(&mut self, data: &[u8]) -> Result<(), RendererError> {
+        self.palette = act_to_palette(data)?;
+        Ok(())
+    }
+}
+
+pub fn run(
+    config: &EngineConfig,
+    output_dir: &Path,
+    format: FrameFormat,
+    max_frames: Option<usize>,
+) -> Result<usize, FrameDumpError> {
+    fs::create_dir_all(output_dir)?;
+    let mut engine = Engine::new(
+        config,
+        Box::new(FrameDumpRenderer::new(output_dir, format)),
+        random::<i16>(),
+    )?;
+    engine.set_uncapped(true);
+
+    while max_frames.is_none_or(|max_frames| engine.frames_presented() < max_frames) {
+        engine.tick(InputState::default())?;
+    }
+    Ok(engine.frames_presented())
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+
+const PAUSE_SLICE: Duration = Duration::from_millis(20);
+
+pub struct FrameLimiter {
+    clock: Box<dyn Clock>,
+    frame_budget: Option<Duration>,
+    last_frame: Duration,
+    last_blit: Duration,
+    speed: f64,
+    pace: f64,
+    uncapped: bool,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<u32>) -> Self {
+        Self::with_clock(max_fps, Box::<SystemClock>::default())
+    }
+
+    pub fn with_clock(max_fps: Option<u32>, clock: Box<dyn Clock>) -> Self {
+        let now = clock.now();
+        Self {
+            clock,
+            frame_budget: max_fps
+                .filter(|&fps| fps > 0)
+                .map(|fps| Duration::from_secs_f64(1.0 / fps as f64)),
+            last_frame: now,
+            last_blit: now,
+            speed: 1.0,
+            pace: 1.0,
+            uncapped: false,
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        let now = clock.now();
+        self.clock = clock;
+        self.last_frame = now;
+        self.last_blit = now;
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        if speed.is_finite() && speed > 0.0 {
+            self.speed = speed;
+        }
+    }
+
+    /// Scales the delays the scripts ask for between blits. Unlike the
+    /// playback speed this is a setting, not a debugging toggle.
+    pub fn set_pace(&mut self, pace: f64) {
+        if pace.is_finite() && pace > 0.0 {
+            self.pace = pace;
+        }
+    }
+
+    pub fn set_uncapped(&mut self, uncapped: bool) {
+        self.uncapped = uncapped;
+    }
+
+    fn elapsed_since(&self, instant: Duration) -> Duration {
+        self.clock.now().saturating_sub(instant)
+    }
+
+    pub fn wait(&mut self) {
+        if let Some(budget) = self.frame_budget.filter(|_| !self.uncapped) {
+            let elapsed = self.elapsed_since(self.last_frame);
+            if elapsed < budget {
+                self.clock.sleep(budget - elapsed);
+            }
+        }
+        self.last_frame = self.clock.now();
+    }
+
+    /// Holds a blit until the number of 20ms slices requested by the script
+    /// has elapsed since the previous one, scaled by the pace multiplier and
+    /// stretched by the playback speed.
+    pub fn wait_for_blit(&mut self, pause_slices: i16) {
+        if !self.uncapped {
+            let target = PAUSE_SLICE
+                .saturating_mul(pause_slices.max(0) as u32)
+                .mul_f64(self.pace)
+                .div_f64(self.speed);
+            self.clock
+                .sleep(target.saturating_sub(self.elapsed_since(self.last_blit)));
+        }
+        self.last_blit = self.clock.now();
+    }
+}
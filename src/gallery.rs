@@ -0,0 +1,62 @@
+use std::{fs, io, path::Path};
+
+use log::warn;
+use strum::IntoEnumIterator;
+
+use crate::{
+    engine::{Engine, EngineConfig, InputState},
+    frame_dump::write_indexed_png,
+    headless::HeadlessRenderer,
+    parts::GamePart,
+    renderer::NUM_COLORS,
+};
+
+const GALLERY_SEED: i16 = 0;
+const GALLERY_MAX_TICKS: usize = 5_000;
+
+/// Boots every part headless and saves its first frame presented with a
+/// non-black palette as `partN.png` in `output_dir`. Parts that cannot be
+/// loaded or never show anything are skipped with a warning. Returns the
+/// number of screenshots written.
+pub fn run(config: &EngineConfig, output_dir: &Path) -> Result<usize, io::Error> {
+    fs::create_dir_all(output_dir)?;
+    let mut written = 0;
+    for part in GamePart::iter() {
+        let number = part as u16 - GamePart::One as u16 + 1;
+        let path = output_dir.join(format!("part{number}.png"));
+        match capture(config, part) {
+            Ok(Some((indexed, palette))) => match write_indexed_png(&path, &indexed, &palette) {
+                Ok(()) => written += 1,
+                Err(e) => warn!("Unable to write {}: {e:?}", path.display()),
+            },
+            Ok(None) => warn!("{part} presented nothing in {GALLERY_MAX_TICKS} ticks, skipping"),
+            Err(e) => warn!("Skipping {part}: {e}"),
+        }
+    }
+    Ok(written)
+}
+
+type Screenshot = (Vec<u8>, [u32; NUM_COLORS]);
+
+fn capture(config: &EngineConfig, part: GamePart) -> Result<Option<Screenshot>, String> {
+    let config = EngineConfig {
+        part: Some(part),
+        ..config.clone()
+    };
+    let mut engine = Engine::new(&config, Box::new(HeadlessRenderer::default()), GALLERY_SEED)
+        .map_err(|e| format!("{e:?}"))?;
+    if engine.current_part() != part {
+        return Err("not available in this data set".to_string());
+    }
+    engine.set_uncapped(true);
+
+    for _ in 0..GALLERY_MAX_TICKS {
+        let frame = engine
+            .tick_frame(InputState::default())
+            .map_err(|e| format!("{e:?}"))?;
+        if frame.present && frame.palette.iter().any(|&rgb| rgb != 0) {
+            return Ok(Some((frame.indexed, frame.palette)));
+        }
+    }
+    Ok(None)
+}
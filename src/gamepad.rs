@@ -0,0 +1,45 @@
+use gilrs::{Axis, Button, Gamepad, Gilrs};
+use log::warn;
+
+use crate::input::InputState;
+
+/// How far the left stick has to be pushed along an axis before it counts
+/// as a direction. Both axes are read independently, which gives the same
+/// eight directions as the arrow keys.
+const STICK_DEADZONE: f32 = 0.5;
+
+pub struct GamepadInput {
+    gilrs: Gilrs,
+}
+
+impl GamepadInput {
+    pub fn new() -> Option<Self> {
+        Gilrs::new()
+            .inspect_err(|e| warn!("Gamepad input unavailable: {e}"))
+            .ok()
+            .map(|gilrs| Self { gilrs })
+    }
+
+    /// Drains the pending events and returns the combined state of every
+    /// connected gamepad.
+    pub fn poll(&mut self) -> InputState {
+        while self.gilrs.next_event().is_some() {}
+        self.gilrs
+            .gamepads()
+            .fold(InputState::default(), |input, (_, gamepad)| {
+                input.merge(Self::read(&gamepad))
+            })
+    }
+
+    fn read(gamepad: &Gamepad) -> InputState {
+        let x = gamepad.value(Axis::LeftStickX);
+        let y = gamepad.value(Axis::LeftStickY);
+        InputState {
+            left: gamepad.is_pressed(Button::DPadLeft) || x <= -STICK_DEADZONE,
+            right: gamepad.is_pressed(Button::DPadRight) || x >= STICK_DEADZONE,
+            up: gamepad.is_pressed(Button::DPadUp) || y >= STICK_DEADZONE,
+            down: gamepad.is_pressed(Button::DPadDown) || y <= -STICK_DEADZONE,
+            action: gamepad.is_pressed(Button::South) || gamepad.is_pressed(Button::East),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::io::Read;
+
+use crate::renderer::{NUM_COLORS, Renderer, RendererError, act_to_palette, read_palette};
+
+#[derive(Default)]
+pub struct HeadlessRenderer {
+    palette: [u32; NUM_COLORS],
+}
+
+impl Renderer for HeadlessRenderer {
+    fn set_palette(&mut self, reader: &mut dyn Read) -> Result<(), RendererError> {
+        self.palette = read_palette(reader)?;
+        Ok(())
+    }
+
+    fn update_display(&mut self, _: &[u8]) -> Result<(), RendererError> {
+        Ok(())
+    }
+
+    fn palette(&self) -> [u32; NUM_COLORS] {
+        self.palette
+    }
+
+    fn import_palette(&mut self, data: &[u8]) -> Result<(), RendererError> {
+        self.palette = act_to_palette(data)?;
+        Ok(())
+    }
+}
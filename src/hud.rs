@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use winit::keyboard::KeyCode;
+
+use crate::{parts::GamePart, vm::FrameCounters};
+
+const HUD_TOGGLE_KEY: KeyCode = KeyCode::F3;
+const FPS_SAMPLE_WINDOW: Duration = Duration::from_secs(1);
+
+pub struct HudStats {
+    pub part: GamePart,
+    pub ready_channels: usize,
+    pub resource: Option<usize>,
+    pub counters: FrameCounters,
+}
+
+pub struct Hud {
+    enabled: bool,
+    window_start: Instant,
+    window_frames: u32,
+    fps: f64,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            window_start: Instant::now(),
+            window_frames: 0,
+            fps: 0.0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key_code: KeyCode) {
+        if key_code == HUD_TOGGLE_KEY {
+            self.enabled = !self.enabled;
+        }
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn record_frame(&mut self) {
+        self.window_frames += 1;
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= FPS_SAMPLE_WINDOW {
+            self.fps = self.window_frames as f64 / elapsed.as_secs_f64();
+            self.window_frames = 0;
+            self.window_start = Instant::now();
+        }
+    }
+
+    pub fn overlay(&self, stats: &HudStats) -> Vec<String> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let resource = stats
+            .resource
+            .map_or_else(|| "-".to_string(), |id| format!("{id:02X}"));
+        vec![
+            format!("FPS {:.1}", self.fps),
+            stats.part.name().to_uppercase(),
+            format!("READY {}", stats.ready_channels),
+            format!("RES {resource}"),
+            format!("OPS {}", stats.counters.opcodes),
+            format!("CHAN {}", stats.counters.channels_run),
+            format!("DRAW {}", stats.counters.draw_calls),
+            format!("POLY {}", stats.counters.polygons),
+            format!(
+                "VERT {}/{}",
+                stats.counters.vertices_filled, stats.counters.vertices_read
+            ),
+        ]
+    }
+}
@@ -0,0 +1,57 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+const LEFT: u8 = 1 << 0;
+const RIGHT: u8 = 1 << 1;
+const UP: u8 = 1 << 2;
+const DOWN: u8 = 1 << 3;
+const ACTION: u8 = 1 << 4;
+
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputSource {
+    #[default]
+    Keyboard,
+    Mouse,
+    Both,
+    /// Keyboard plus any connected controller. Needs the `gamepad` feature.
+    Gamepad,
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct InputState {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub action: bool,
+}
+
+impl InputState {
+    pub fn to_bits(self) -> u8 {
+        [
+            (self.left, LEFT),
+            (self.right, RIGHT),
+            (self.up, UP),
+            (self.down, DOWN),
+            (self.action, ACTION),
+        ]
+        .iter()
+        .filter(|(pressed, _)| *pressed)
+        .fold(0, |bits, (_, bit)| bits | bit)
+    }
+
+    pub fn merge(self, other: InputState) -> Self {
+        Self::from_bits(self.to_bits() | other.to_bits())
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            left: bits & LEFT != 0,
+            right: bits & RIGHT != 0,
+            up: bits & UP != 0,
+            down: bits & DOWN != 0,
+            action: bits & ACTION != 0,
+        }
+    }
+}
@@ -1,15 +1,51 @@
-mod bank;
+mod attract;
+pub mod audio;
+pub mod bank;
+pub mod bank_check;
+pub mod benchmark;
 mod channel;
+pub mod checkpoints;
+pub mod clock;
+pub mod color_vision;
+pub mod compat;
+pub mod coverage;
+mod debugger;
+pub mod disasm;
 pub mod engine;
-mod execution_context;
-mod loaded;
+pub mod execution_context;
+pub mod frame_dump;
+mod frame_limiter;
+pub mod gallery;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod headless;
+mod hud;
+mod input;
+pub mod loaded;
 mod mem_entry;
+pub mod mem_report;
+mod menu;
 mod opcodes;
-mod parts;
-mod renderer;
+pub mod part_list;
+pub mod parts;
+pub mod presets;
+mod recording;
+#[cfg(feature = "profile")]
+mod render_profile;
+pub mod renderer;
 mod resource;
+mod rewind;
+pub mod segment_dump;
+pub mod self_test;
 mod shapes;
+mod snapshot;
+mod sound;
 mod sys_event_handler;
+mod terminal;
+#[cfg(test)]
+mod test_data;
 mod text;
+mod trace;
+pub mod verify;
 mod video;
-mod vm;
+pub mod vm;
@@ -1,8 +1,14 @@
-use std::{collections::HashMap, io::Cursor};
+use std::{
+    collections::HashMap,
+    io::{Cursor, Seek, SeekFrom},
+};
 
 use thiserror::Error;
 
-use crate::parts::Segment::{self, Bytecode, Palette, PolyCinematic, Polygon};
+use crate::{
+    parts::Segment::{self, Bytecode, Palette, PolyCinematic, Polygon},
+    vm::VmError,
+};
 
 macro_rules! extract_required {
     ($map:expr, $segment:expr) => {
@@ -17,12 +23,16 @@ pub enum LoadedPartError {
     MissingSegment(Segment),
 }
 
+/// Backing stream of a loaded segment. Segment consumers only require
+/// `Read + Seek`, so this is the single place to swap in another storage.
+pub type SegmentStream = Cursor<Vec<u8>>;
+
 #[derive(Default)]
 pub struct LoadedPart {
-    pub bytecode: Cursor<Vec<u8>>,
-    pub palette: Cursor<Vec<u8>>,
-    pub cinematic: Cursor<Vec<u8>>,
-    pub polygon: Option<Cursor<Vec<u8>>>,
+    pub bytecode: SegmentStream,
+    pub palette: SegmentStream,
+    pub cinematic: SegmentStream,
+    pub polygon: Option<SegmentStream>,
 }
 
 impl LoadedPart {
@@ -32,17 +42,81 @@ impl LoadedPart {
         let cinematic = extract_required!(segment_data, PolyCinematic);
         let polygon = segment_data.remove(&Polygon);
         let loaded_part = Self {
-            bytecode: Cursor::new(bytecode),
-            palette: Cursor::new(palette),
-            cinematic: Cursor::new(cinematic),
-            polygon: polygon.map(Cursor::new),
+            bytecode: SegmentStream::new(bytecode),
+            palette: SegmentStream::new(palette),
+            cinematic: SegmentStream::new(cinematic),
+            polygon: polygon.map(SegmentStream::new),
         };
         Ok(loaded_part)
     }
+
+    /// A part made of `bytecode` alone, with empty palette and cinematic
+    /// segments.
+    pub fn from_bytecode(bytecode: Vec<u8>) -> Self {
+        Self {
+            bytecode: SegmentStream::new(bytecode),
+            palette: SegmentStream::default(),
+            cinematic: SegmentStream::default(),
+            polygon: None,
+        }
+    }
+
+    pub fn segment(&self, segment: Segment) -> Option<&[u8]> {
+        let stream = match segment {
+            Bytecode => Some(&self.bytecode),
+            Palette => Some(&self.palette),
+            PolyCinematic => Some(&self.cinematic),
+            Polygon => self.polygon.as_ref(),
+        };
+        stream.map(|stream| stream.get_ref().as_slice())
+    }
+
+    pub fn has_polygon(&self) -> bool {
+        self.polygon.is_some()
+    }
+
+    pub fn bytecode_len(&self) -> usize {
+        self.bytecode.get_ref().len()
+    }
+
+    /// Rejects jump, call and channel targets taken from game data that fall
+    /// outside the bytecode segment.
+    pub fn check_bytecode_offset(&self, offset: usize) -> Result<(), VmError> {
+        let len = self.bytecode_len();
+        if offset >= len {
+            return Err(VmError::InvalidBytecodeOffset { offset, len });
+        }
+        Ok(())
+    }
+
+    pub fn seek_bytecode(&mut self, offset: usize) -> Result<(), VmError> {
+        self.check_bytecode_offset(offset)?;
+        self.bytecode.seek(SeekFrom::Start(offset as u64))?;
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        [Bytecode, Palette, PolyCinematic, Polygon]
+            .into_iter()
+            .filter_map(|segment| self.segment(segment))
+            .map(<[u8]>::len)
+            .sum()
+    }
 }
 
 type MemEntryIndex = usize;
 #[derive(Default)]
 pub struct LoadedAsset {
     pub assets: HashMap<MemEntryIndex, Vec<u8>>,
+    pub last_loaded: Option<MemEntryIndex>,
+}
+
+impl LoadedAsset {
+    pub fn indices(&self) -> impl Iterator<Item = MemEntryIndex> + '_ {
+        self.assets.keys().copied()
+    }
+
+    pub fn size(&self) -> usize {
+        self.assets.values().map(Vec::len).sum()
+    }
 }
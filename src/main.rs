@@ -1,22 +1,542 @@
-use std::path::PathBuf;
+use std::{error::Error, fs, path::PathBuf, process, time::Duration};
 
-use another_rusty_world::engine::Engine;
-use clap::Parser;
+use another_rusty_world::{
+    bank_check, benchmark,
+    checkpoints::{self, CHECKPOINTS},
+    color_vision::ColorVision,
+    disasm,
+    engine::{
+        BlendMode, DEFAULT_ATTRACT_SECONDS, DEFAULT_MAX_CALL_DEPTH, DEFAULT_MEM_LIST_FILE,
+        DEFAULT_REWIND_FRAMES, DEFAULT_WATCHDOG_LIMIT, Engine, EngineConfig, ErrorPolicy,
+        GameVersion, InputSource, InternalScale, Language, MemListFormat, Quirk, RendererKind,
+        VariableAssignment, WindowPosition, find_data_dir,
+    },
+    frame_dump::{self, FrameFormat},
+    gallery, mem_report, part_list,
+    parts::{GamePart, Segment},
+    presets::Preset,
+    renderer::{Aspect, DEFAULT_SCALE, Filter},
+    segment_dump, self_test,
+    verify::{self, DEFAULT_VERIFY_FRAMES},
+};
+use clap::{ArgGroup, ArgMatches, CommandFactory, FromArgMatches, Parser, parser::ValueSource};
 use log::{error, info};
+use serde::Deserialize;
 
-#[derive(Parser, Debug)]
+const DEFAULT_DATA_DIR: &str = "./another_world";
+
+macro_rules! merge_config_file {
+    ($args:expr, $file_args:expr, $matches:expr, [$($field:ident),* $(,)?]) => {
+        $(
+            if $matches.value_source(stringify!($field)) != Some(ValueSource::CommandLine) {
+                $args.$field = $file_args.$field;
+            }
+        )*
+    };
+}
+
+#[derive(Parser, Deserialize, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("frame_capped").args(["dump_frames", "dump_frames_raw", "verify"])))]
+#[serde(default, deny_unknown_fields)]
 struct Args {
-    #[arg(short, long, default_value = "./another_world")]
-    data_dir: String,
+    #[arg(long, value_name = "FILE")]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+    #[arg(short, long)]
+    data_dir: Option<PathBuf>,
+    /// Name of the resource list in the data directory
+    #[arg(long, value_name = "NAME", default_value = DEFAULT_MEM_LIST_FILE)]
+    memlist: String,
+    /// Size fields of the resource list, wide for resources over 64KB
+    #[arg(long, value_enum, default_value_t = MemListFormat::Classic)]
+    memlist_format: MemListFormat,
+    /// Load unpacked resources named like 0x15.bin from this directory
+    #[arg(long = "override", value_name = "DIR")]
+    override_dir: Option<PathBuf>,
+    #[arg(short, long, value_enum, default_value_t = RendererKind::Window)]
+    renderer: RendererKind,
+    #[arg(long, value_name = "FILE", conflicts_with = "play")]
+    record: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    play: Option<PathBuf>,
+    /// Write a CSV line per frame with the part and VM counters
+    #[arg(long, value_name = "FILE")]
+    trace_file: Option<PathBuf>,
+    #[arg(long, conflicts_with = "with_protection")]
+    skip_intro: bool,
+    /// Start at a game part, 1-10 or 0x3E80-0x3E89
+    #[arg(long, value_name = "PART", conflicts_with_all = ["with_protection", "skip_intro", "checkpoint"])]
+    part: Option<GamePart>,
+    /// Resume from a state snapshot saved with F12
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["checkpoint", "skip_intro"])]
+    load_state: Option<PathBuf>,
+    /// Start at a named checkpoint, see --list-checkpoints
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["with_protection", "skip_intro"])]
+    checkpoint: Option<String>,
+    #[arg(long, value_enum, default_value_t = InputSource::Keyboard)]
+    input: InputSource,
+    #[arg(long, value_name = "FPS")]
+    max_fps: Option<u32>,
+    #[arg(long)]
+    debug_console: bool,
+    #[arg(long, value_name = "FRAMES")]
+    benchmark: Option<usize>,
+    /// Also print p50/p95/p99 frame times
+    #[arg(long, requires = "benchmark")]
+    percentiles: bool,
+    #[arg(long, value_enum, default_value_t = ErrorPolicy::Abort)]
+    error_policy: ErrorPolicy,
+    #[arg(long)]
+    with_protection: bool,
+    #[arg(long, value_enum, default_value_t = GameVersion::Dos)]
+    game_version: GameVersion,
+    #[arg(long, value_enum, default_value_t = BlendMode::Authentic)]
+    blend_mode: BlendMode,
+    /// Resolution polygons are drawn at, as a multiple of 320x200
+    #[arg(long, value_enum, default_value_t = InternalScale::X1)]
+    internal_scale: InternalScale,
+    /// Revert fixes, comma separated, to compare scenes with older builds
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "QUIRKS")]
+    compat: Vec<Quirk>,
+    #[arg(long, value_enum, default_value_t = Filter::None)]
+    filter: Filter,
+    #[arg(long, value_enum, default_value_t = Aspect::Square)]
+    aspect: Aspect,
+    #[arg(long, value_name = "FACTOR", default_value_t = DEFAULT_SCALE,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    #[arg(long, value_name = "X,Y")]
+    window_pos: Option<WindowPosition>,
+    #[arg(long)]
+    always_on_top: bool,
+    /// Pace presents to the monitor refresh rate
+    #[arg(long)]
+    vsync: bool,
+    /// Smooth polygon edges in the window renderer
+    #[arg(long)]
+    aa: bool,
+    /// Blend palette changes over this many frames (not authentic)
+    #[arg(long, value_name = "FRAMES", default_value_t = 0)]
+    palette_fade: usize,
+    /// Page backgrounds are decoded into and color 0x11 copies from
+    #[arg(long, value_name = "PAGE", default_value_t = 0,
+          value_parser = clap::value_parser!(u8).range(0..4))]
+    background_page: u8,
+    /// Run the VM without presenting frames
+    #[arg(long, conflicts_with_all = ["dump_frames", "dump_frames_raw"])]
+    skip_present: bool,
+    #[arg(long, value_name = "DIR", conflicts_with = "benchmark")]
+    dump_frames: Option<PathBuf>,
+    /// Dump frames as raw palette indices along with the palette
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["benchmark", "dump_frames"])]
+    dump_frames_raw: Option<PathBuf>,
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["benchmark", "dump_frames", "dump_frames_raw"])]
+    gallery: Option<PathBuf>,
+    #[arg(long, value_name = "N", requires = "frame_capped")]
+    max_frames: Option<usize>,
+    #[arg(long, value_name = "PART", conflicts_with_all = ["benchmark", "dump_frames", "dump_frames_raw"])]
+    mem_report: Option<GamePart>,
+    #[arg(long, value_name = "PART", conflicts_with_all = ["benchmark", "dump_frames", "dump_frames_raw", "mem_report"])]
+    verify: Option<GamePart>,
+    #[arg(long, value_name = "VAR=VALUE")]
+    set_var: Vec<VariableAssignment>,
+    #[arg(long, value_name = "OPCODES", default_value_t = DEFAULT_WATCHDOG_LIMIT)]
+    watchdog_limit: usize,
+    #[arg(long, value_name = "DEPTH", default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
+    /// Log and step over opcodes the VM does not know instead of failing
+    #[arg(long)]
+    skip_unknown_opcodes: bool,
+    /// Play without opening an audio output
+    #[arg(long)]
+    mute: bool,
+    /// Start with the music muted, M toggles it
+    #[arg(long)]
+    mute_music: bool,
+    /// Start with the sound effects muted, X toggles them
+    #[arg(long)]
+    mute_sfx: bool,
+    /// Play music from OGG files named like 0x07.ogg in this directory
+    #[arg(long, value_name = "DIR")]
+    music_pack: Option<PathBuf>,
+    #[arg(long, value_name = "OPCODES")]
+    preempt_budget: Option<usize>,
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_factor)]
+    speed: f64,
+    /// Scale the delays the game waits between frames, 1.0 is authentic
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_factor)]
+    pace_multiplier: f64,
+    #[arg(long, value_enum, default_value_t = ColorVision::None)]
+    cvd: ColorVision,
+    #[arg(long, value_enum, default_value_t = Language::En)]
+    lang: Language,
+    #[arg(long)]
+    opcode_coverage: bool,
+    #[arg(long)]
+    attract: bool,
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_ATTRACT_SECONDS, requires = "attract")]
+    attract_seconds: u64,
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_REWIND_FRAMES)]
+    rewind_frames: usize,
+    #[arg(long)]
+    #[serde(skip)]
+    list_parts: bool,
+    #[arg(long)]
+    #[serde(skip)]
+    list_checkpoints: bool,
+    #[arg(long)]
+    #[serde(skip)]
+    check_banks: bool,
+    #[arg(long, num_args = 2, value_names = ["PART", "FILE"])]
+    #[serde(skip)]
+    dump_bytecode: Option<Vec<String>>,
+    /// Print the disassembled bytecode of a part and exit
+    #[arg(long, value_name = "PART")]
+    #[serde(skip)]
+    disasm: Option<GamePart>,
+    /// Unpack an embedded resource to check the decoder, no data needed
+    #[arg(long)]
+    #[serde(skip)]
+    selftest: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::parse_from([env!("CARGO_PKG_NAME")])
+    }
+}
+
+impl Args {
+    fn load() -> Result<Self, String> {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).map_err(|e| e.to_string())?;
+        if let Some(path) = &args.config {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+            let file_args: Self = toml::from_str(&content)
+                .map_err(|e| format!("Invalid config file {}: {e}", path.display()))?;
+            args.merge(file_args, &matches);
+        }
+        Ok(args)
+    }
+
+    fn merge(&mut self, file_args: Self, matches: &ArgMatches) {
+        merge_config_file!(
+            self,
+            file_args,
+            matches,
+            [
+                data_dir,
+                memlist,
+                memlist_format,
+                override_dir,
+                renderer,
+                record,
+                play,
+                trace_file,
+                skip_intro,
+                checkpoint,
+                input,
+                max_fps,
+                debug_console,
+                benchmark,
+                percentiles,
+                error_policy,
+                with_protection,
+                game_version,
+                blend_mode,
+                internal_scale,
+                compat,
+                filter,
+                aspect,
+                scale,
+                window_pos,
+                always_on_top,
+                vsync,
+                aa,
+                palette_fade,
+                background_page,
+                skip_present,
+                dump_frames,
+                dump_frames_raw,
+                gallery,
+                max_frames,
+                mem_report,
+                verify,
+                set_var,
+                watchdog_limit,
+                max_call_depth,
+                skip_unknown_opcodes,
+                mute,
+                mute_music,
+                mute_sfx,
+                music_pack,
+                load_state,
+                part,
+                preempt_budget,
+                speed,
+                pace_multiplier,
+                cvd,
+                lang,
+                opcode_coverage,
+                attract,
+                attract_seconds,
+                rewind_frames,
+            ]
+        );
+    }
+}
+
+fn parse_factor(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(factor),
+        _ => Err(format!("Invalid factor {raw}, expected a positive number")),
+    }
+}
+
+/// Formats an error followed by each of its sources, outermost first.
+fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+fn data_dir_candidates() -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from("."), PathBuf::from(DEFAULT_DATA_DIR)];
+    if let Some(config_dir) = dirs::config_dir() {
+        candidates.push(config_dir.join(env!("CARGO_PKG_NAME")));
+    }
+    candidates
+}
+
+fn resolve_data_dir(data_dir: Option<PathBuf>, mem_list_file: &str) -> Result<PathBuf, String> {
+    if let Some(data_dir) = data_dir {
+        return Ok(data_dir);
+    }
+    let candidates = data_dir_candidates();
+    match find_data_dir(&candidates, mem_list_file) {
+        Some(data_dir) => {
+            info!("Using data directory {}", data_dir.display());
+            Ok(data_dir.clone())
+        }
+        None => Err(format!(
+            "No data set found, checked: {}",
+            candidates
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
 }
 
 fn main() {
-    let args = Args::parse();
     env_logger::init();
+    let args = Args::load().unwrap_or_else(|e| {
+        error!("{e}");
+        process::exit(2);
+    });
+
+    if args.selftest {
+        match self_test::run() {
+            Ok(()) => println!("Self-test passed"),
+            Err(e) => {
+                println!("Self-test failed: {}", error_chain(&e));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.list_checkpoints {
+        CHECKPOINTS
+            .iter()
+            .for_each(|checkpoint| println!("{checkpoint}"));
+        return;
+    }
+
+    let preset = match args.checkpoint.as_deref() {
+        Some(name) => {
+            let checkpoint = checkpoints::find(name).unwrap_or_else(|| {
+                error!("No checkpoint named {name}, see --list-checkpoints");
+                process::exit(2);
+            });
+            Some(Preset::Checkpoint(checkpoint))
+        }
+        None => args.skip_intro.then_some(Preset::SkipIntro),
+    };
+
+    let data_dir = resolve_data_dir(args.data_dir, &args.memlist).unwrap_or_else(|e| {
+        error!("{e}");
+        process::exit(2);
+    });
+
+    if args.list_parts {
+        match part_list::run(&data_dir, &args.memlist) {
+            Ok(parts) => parts.iter().for_each(|part| println!("{part}")),
+            Err(e) => error!("Unable to list parts. Error: {}", error_chain(&e)),
+        }
+        return;
+    }
+
+    if let Some(part) = args.disasm {
+        match disasm::run(&data_dir, &args.memlist, part) {
+            Ok(instructions) => instructions.iter().for_each(|i| println!("{i}")),
+            Err(e) => {
+                error!("Unable to disassemble {part}. Error: {}", error_chain(&e));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some([part, path]) = args.dump_bytecode.as_deref() {
+        let part = part.parse::<GamePart>().unwrap_or_else(|e| {
+            error!("{e}");
+            process::exit(2);
+        });
+        let path = PathBuf::from(path);
+        match segment_dump::run(&data_dir, &args.memlist, part, Segment::Bytecode, &path) {
+            Ok(len) => info!("Wrote {len} bytes of {part} bytecode to {}", path.display()),
+            Err(e) => {
+                error!("Unable to dump the bytecode. Error: {}", error_chain(&e));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.check_banks {
+        match bank_check::run(&data_dir, &args.memlist) {
+            Ok(report) => {
+                println!("{report}");
+                if !report.passed() {
+                    process::exit(1);
+                }
+            }
+            Err(e) => {
+                error!("Unable to check banks. Error: {}", error_chain(&e));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let config = EngineConfig {
+        data_dir,
+        mem_list: args.memlist,
+        mem_list_format: args.memlist_format,
+        override_dir: args.override_dir,
+        renderer: args.renderer,
+        record: args.record,
+        play: args.play,
+        trace_file: args.trace_file,
+        preset,
+        part: args.part,
+        variables: args.set_var,
+        watchdog_limit: args.watchdog_limit,
+        max_call_depth: args.max_call_depth,
+        skip_unknown_opcodes: args.skip_unknown_opcodes,
+        mute: args.mute,
+        mute_music: args.mute_music,
+        mute_sfx: args.mute_sfx,
+        music_pack: args.music_pack,
+        load_state: args.load_state,
+        preempt_budget: args.preempt_budget,
+        speed: args.speed,
+        pace_multiplier: args.pace_multiplier,
+        color_vision: args.cvd,
+        language: args.lang,
+        max_fps: args.max_fps,
+        debug_console: args.debug_console,
+        error_policy: args.error_policy,
+        with_protection: args.with_protection,
+        game_version: args.game_version,
+        blend_mode: args.blend_mode,
+        internal_scale: args.internal_scale,
+        compat: args.compat,
+        filter: args.filter,
+        aspect: args.aspect,
+        scale: args.scale,
+        window_pos: args.window_pos,
+        always_on_top: args.always_on_top,
+        vsync: args.vsync,
+        antialias: args.aa,
+        palette_fade: args.palette_fade,
+        background_page: args.background_page,
+        opcode_coverage: args.opcode_coverage,
+        attract: args
+            .attract
+            .then(|| Duration::from_secs(args.attract_seconds)),
+        rewind_frames: args.rewind_frames,
+        skip_present: args.skip_present,
+        input_source: args.input,
+    };
+    if let Some(part) = args.mem_report {
+        match mem_report::run(&config, part) {
+            Ok(report) => println!("{report}"),
+            Err(e) => error!(
+                "Memory report terminated abruptly. Error: {}",
+                error_chain(&e)
+            ),
+        }
+        return;
+    }
+
+    if let Some(part) = args.verify {
+        let report = verify::run(
+            &config,
+            part,
+            args.max_frames.unwrap_or(DEFAULT_VERIFY_FRAMES),
+        );
+        println!("{report}");
+        if !report.passed() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let frame_dump = match (args.dump_frames, args.dump_frames_raw) {
+        (Some(output_dir), _) => Some((output_dir, FrameFormat::Png)),
+        (None, Some(output_dir)) => Some((output_dir, FrameFormat::Raw)),
+        (None, None) => None,
+    };
+    if let Some((output_dir, format)) = frame_dump {
+        match frame_dump::run(&config, &output_dir, format, args.max_frames) {
+            Ok(frames) => info!("Dumped {frames} frames to {}", output_dir.display()),
+            Err(e) => error!("Frame dump terminated abruptly. Error: {}", error_chain(&e)),
+        }
+        return;
+    }
+
+    if let Some(output_dir) = args.gallery {
+        match gallery::run(&config, &output_dir) {
+            Ok(count) => info!("Saved {count} screenshots to {}", output_dir.display()),
+            Err(e) => error!("Unable to create the gallery. Error: {}", error_chain(&e)),
+        }
+        return;
+    }
+
+    if let Some(frames) = args.benchmark {
+        match benchmark::run(&config, frames) {
+            Ok(report) => {
+                println!("{report}");
+                if args.percentiles {
+                    println!("{}", report.percentiles());
+                }
+            }
+            Err(e) => error!("Benchmark terminated abruptly. Error: {}", error_chain(&e)),
+        }
+        return;
+    }
 
-    if let Err(e) = Engine::run(PathBuf::from(args.data_dir)) {
-        error!("Engine terminated abruptly. Error: {:?}", e);
+    if let Err(e) = Engine::run(config) {
+        error!("Engine terminated abruptly. Error: {}", error_chain(&e));
         return;
     }
     info!("Execution terminated successfully");
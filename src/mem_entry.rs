@@ -1,12 +1,14 @@
 use std::io;
 
 use byteorder::{BigEndian, ReadBytesExt};
+use clap::ValueEnum;
+use serde::Deserialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum MemEntryError {
     #[error("Error while reading the underlying stream")]
-    Io(io::Error),
+    Io(#[source] io::Error),
     #[error("Invalid resource status: {0}")]
     InvalidState(u8),
     #[error("Invalid resource type: {0}")]
@@ -19,29 +21,60 @@ impl From<io::Error> for MemEntryError {
     }
 }
 
-#[derive(Debug)]
+/// Status byte of the entry that terminates `memlist.bin`.
+pub const END_OF_LIST: u8 = 0xFF;
+
+/// How the sizes of a memlist entry are stored. Each is a big-endian word
+/// preceded by a word the original releases leave at zero. `Classic` reads
+/// the low word only, like the original engine, capping resources at 64KB.
+/// `Wide` reads both words as one 32-bit size for data sets that go past
+/// that cap.
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MemListFormat {
+    #[default]
+    Classic,
+    Wide,
+}
+
+impl MemListFormat {
+    fn read_size<R: ReadBytesExt>(self, reader: &mut R) -> io::Result<u32> {
+        match self {
+            MemListFormat::Classic => {
+                reader.read_u16::<BigEndian>()?;
+                Ok(reader.read_u16::<BigEndian>()?.into())
+            }
+            MemListFormat::Wide => reader.read_u32::<BigEndian>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct MemEntry {
+    pub status: u8,
     pub bank_id: u8,
     pub bank_offset: u32,
-    pub packed_size: u16,
-    pub size: u16,
+    pub packed_size: u32,
+    pub size: u32,
 }
 
 impl MemEntry {
-    pub fn from_reader<R: ReadBytesExt>(reader: &mut R) -> Result<Self, MemEntryError> {
-        reader.read_u8()?;
+    pub fn from_reader<R: ReadBytesExt>(
+        reader: &mut R,
+        format: MemListFormat,
+    ) -> Result<Self, MemEntryError> {
+        let status = reader.read_u8()?;
         reader.read_u8()?;
         reader.read_u16::<BigEndian>()?;
         reader.read_u16::<BigEndian>()?;
         reader.read_u8()?;
         let bank_id = reader.read_u8()?;
         let bank_offset = reader.read_u32::<BigEndian>()?;
-        reader.read_u16::<BigEndian>()?;
-        let packed_size = reader.read_u16::<BigEndian>()?;
-        reader.read_u16::<BigEndian>()?;
-        let size = reader.read_u16::<BigEndian>()?;
+        let packed_size = format.read_size(reader)?;
+        let size = format.read_size(reader)?;
 
         let mem_entry = MemEntry {
+            status,
             bank_id,
             bank_offset,
             packed_size,
@@ -49,4 +82,8 @@ impl MemEntry {
         };
         Ok(mem_entry)
     }
+
+    pub fn is_end_of_list(&self) -> bool {
+        self.status == END_OF_LIST
+    }
 }
@@ -0,0 +1,67 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    engine::{Engine, EngineConfig, EngineError},
+    headless::HeadlessRenderer,
+    input::InputState,
+    parts::GamePart,
+};
+
+const MEM_REPORT_SEED: i16 = 0;
+const MEM_REPORT_FRAMES: usize = 100;
+
+pub struct MemoryUsage {
+    pub segments: usize,
+    pub assets: usize,
+}
+
+pub struct MemReport {
+    part: GamePart,
+    segments: usize,
+    peak_assets: usize,
+    eager: usize,
+}
+
+impl Display for MemReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let peak = self.segments + self.peak_assets;
+        writeln!(f, "part:        {}", self.part)?;
+        writeln!(f, "segments:    {} bytes", self.segments)?;
+        writeln!(f, "assets peak: {} bytes", self.peak_assets)?;
+        writeln!(f, "total peak:  {peak} bytes")?;
+        write!(
+            f,
+            "eager:       {} bytes ({} bytes saved)",
+            self.eager,
+            self.eager.saturating_sub(peak)
+        )
+    }
+}
+
+pub fn run(config: &EngineConfig, part: GamePart) -> Result<MemReport, EngineError> {
+    let config = EngineConfig {
+        part: Some(part),
+        ..config.clone()
+    };
+    let mut engine = Engine::new(
+        &config,
+        Box::new(HeadlessRenderer::default()),
+        MEM_REPORT_SEED,
+    )?;
+    engine.set_uncapped(true);
+
+    let mut segments = 0;
+    let mut peak_assets = 0;
+    for _ in 0..MEM_REPORT_FRAMES {
+        engine.tick(InputState::default())?;
+        let usage = engine.memory_usage();
+        segments = usage.segments;
+        peak_assets = peak_assets.max(usage.assets);
+    }
+    Ok(MemReport {
+        part,
+        segments,
+        peak_assets,
+        eager: engine.eager_memory_usage(),
+    })
+}
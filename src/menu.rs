@@ -0,0 +1,52 @@
+use winit::keyboard::KeyCode;
+
+use crate::parts::GamePart;
+
+pub const MENU_KEY: KeyCode = KeyCode::Escape;
+
+/// A list of parts to restart from, drawn over the paused game.
+#[derive(Default)]
+pub struct PartMenu {
+    parts: Vec<GamePart>,
+    selected: usize,
+    open: bool,
+}
+
+impl PartMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the menu on `parts`, highlighting `current` if it is listed.
+    pub fn open(&mut self, parts: Vec<GamePart>, current: GamePart) {
+        self.selected = parts.iter().position(|&part| part == current).unwrap_or(0);
+        self.parts = parts;
+        self.open = true;
+    }
+
+    /// Moves the selection, or closes the menu and returns the chosen part.
+    /// `MENU_KEY` closes it without choosing.
+    pub fn handle_key(&mut self, key_code: KeyCode) -> Option<GamePart> {
+        let count = self.parts.len().max(1);
+        match key_code {
+            MENU_KEY => self.open = false,
+            KeyCode::ArrowUp => self.selected = (self.selected + count - 1) % count,
+            KeyCode::ArrowDown => self.selected = (self.selected + 1) % count,
+            KeyCode::Enter | KeyCode::Space => {
+                self.open = false;
+                return self.parts.get(self.selected).copied();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn overlay(&self) -> Vec<String> {
+        let mut lines = vec!["SELECT A PART".to_string(), String::new()];
+        lines.extend(self.parts.iter().enumerate().map(|(index, part)| {
+            let marker = if index == self.selected { '>' } else { ' ' };
+            format!("{marker} {}", part.name().to_uppercase())
+        }));
+        lines
+    }
+}
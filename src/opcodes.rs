@@ -34,3 +34,33 @@ pub static OPCODE_TABLE: &[OpcodeHandler] = &[
     Vm::op_update_mem_list,
     Vm::op_play_music,
 ];
+
+pub static OPCODE_NAMES: &[&str] = &[
+    "mov_const",
+    "mov",
+    "add",
+    "add_const",
+    "call",
+    "ret",
+    "yield_channel",
+    "jmp",
+    "set_next_pc",
+    "jnz",
+    "cond_jmp",
+    "set_palette",
+    "reset_threads",
+    "select_page",
+    "fill_page",
+    "copy_page",
+    "blit",
+    "kill_channel",
+    "draw_string",
+    "sub",
+    "and",
+    "or",
+    "shl",
+    "shr",
+    "play_sound",
+    "update_mem_list",
+    "play_music",
+];
@@ -0,0 +1,57 @@
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+use strum::IntoEnumIterator;
+
+use crate::{engine::EngineError, parts::GamePart, resource::ResourceRegistry};
+
+pub struct PartInfo {
+    part: GamePart,
+    segments: [usize; 4],
+    problems: Vec<String>,
+}
+
+impl Display for PartInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [palette, bytecode, cinematic, polygon] = self.segments;
+        write!(
+            f,
+            "{:<10} {:#06X}  {palette:02X} {bytecode:02X} {cinematic:02X} {polygon:02X}  ",
+            self.part.name(),
+            self.part as u16,
+        )?;
+        if self.problems.is_empty() {
+            write!(f, "ok")
+        } else {
+            write!(f, "unavailable: {}", self.problems.join("; "))
+        }
+    }
+}
+
+pub fn run(data_dir: &Path, mem_list_file: &str) -> Result<Vec<PartInfo>, EngineError> {
+    let mut resource = ResourceRegistry::new(data_dir.to_path_buf(), mem_list_file);
+    resource.read_entries()?;
+
+    Ok(GamePart::iter()
+        .map(|part| {
+            let segments = ResourceRegistry::segment_indices(part);
+            let problems = segments
+                .iter()
+                .filter(|&&index| index != 0)
+                .filter_map(|&index| {
+                    resource
+                        .check_entry(index)
+                        .err()
+                        .map(|e| format!("entry {index:02X}: {e:?}"))
+                })
+                .collect();
+            PartInfo {
+                part,
+                segments,
+                problems,
+            }
+        })
+        .collect())
+}
@@ -1,5 +1,11 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use strum::EnumCount;
+use serde::{Deserialize, Deserializer, de};
+use strum::{EnumCount, EnumIter};
 
 #[derive(IntoPrimitive, TryFromPrimitive, PartialEq, Eq, Hash, Copy, Clone, Debug)]
 #[repr(u8)]
@@ -10,7 +16,9 @@ pub enum Segment {
     Polygon,
 }
 
-#[derive(Copy, Clone, IntoPrimitive, TryFromPrimitive, EnumCount)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, IntoPrimitive, TryFromPrimitive, EnumCount, EnumIter,
+)]
 #[repr(u16)]
 pub enum GamePart {
     One = 0x3E80,
@@ -26,6 +34,87 @@ pub enum GamePart {
 }
 
 const NUM_PARTS: usize = GamePart::COUNT;
+
+impl GamePart {
+    /// The part the story moves on to once this one is completed. The
+    /// password parts and the final part have no fixed successor.
+    pub fn successor(self) -> Option<GamePart> {
+        if (self as u16) < GamePart::Eigth as u16 {
+            GamePart::try_from_primitive(self as u16 + 1).ok()
+        } else {
+            None
+        }
+    }
+
+    /// The last story part. The game ends once its channels all stop.
+    pub fn is_final(self) -> bool {
+        self == GamePart::Eigth
+    }
+
+    /// Human-readable name, independent of the variant identifiers (some of
+    /// which are misspelled but kept for compatibility).
+    pub fn name(self) -> &'static str {
+        match self {
+            GamePart::One => "Part One",
+            GamePart::Two => "Part Two",
+            GamePart::Tree => "Part Three",
+            GamePart::Four => "Part Four",
+            GamePart::Five => "Part Five",
+            GamePart::Six => "Part Six",
+            GamePart::Seven => "Part Seven",
+            GamePart::Eigth => "Part Eight",
+            GamePart::Nine => "Part Nine",
+            GamePart::Ten => "Part Ten",
+        }
+    }
+}
+
+impl Display for GamePart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for GamePart {
+    type Err = String;
+
+    /// Accepts either the part number (1-10) or the raw resource id
+    /// (0x3E80-0x3E89, in hex or decimal).
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let number = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => value.parse::<u16>(),
+        }
+        .map_err(|_| format!("Invalid game part {value}"))?;
+        let raw_id = match number {
+            1..=10 => GamePart::One as u16 + number - 1,
+            _ => number,
+        };
+        GamePart::try_from_primitive(raw_id)
+            .map_err(|_| format!("Invalid game part {value}, expected 1-10 or 0x3E80-0x3E89"))
+    }
+}
+
+impl<'de> Deserialize<'de> for GamePart {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawPart {
+            Number(u16),
+            Text(String),
+        }
+        match RawPart::deserialize(deserializer)? {
+            RawPart::Number(number) => number.to_string().parse(),
+            RawPart::Text(text) => text.parse(),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+/// Memlist indices of each part's palette, bytecode, cinematic polygons and
+/// shared polygons. Nine and Ten both point at the password screen, exactly
+/// as in the original interpreter's part table.
 pub static SEGMENT_IDX_BY_PART: [[usize; 4]; NUM_PARTS] = [
     [0x14, 0x15, 0x16, 0x00],
     [0x17, 0x18, 0x19, 0x00],
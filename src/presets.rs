@@ -0,0 +1,21 @@
+use crate::{checkpoints::Checkpoint, parts::GamePart};
+
+const VM_VARIABLE_CHECKPOINT: usize = 0x00;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Preset {
+    SkipIntro,
+    Checkpoint(&'static Checkpoint),
+}
+
+impl Preset {
+    pub fn resolve(self) -> (GamePart, Vec<(usize, i16)>) {
+        match self {
+            Preset::SkipIntro => (GamePart::Tree, vec![(VM_VARIABLE_CHECKPOINT, 0)]),
+            Preset::Checkpoint(checkpoint) => (
+                checkpoint.part,
+                vec![(VM_VARIABLE_CHECKPOINT, checkpoint.position)],
+            ),
+        }
+    }
+}
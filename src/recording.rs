@@ -0,0 +1,153 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
+use thiserror::Error;
+
+use crate::input::InputState;
+
+const MAGIC: &[u8; 4] = b"ARWI";
+const VERSION: u8 = 1;
+const FRAME_COUNT_OFFSET: u64 = 7;
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("IO error while accessing the recording")]
+    Io(#[source] io::Error),
+    #[error("Not an input recording")]
+    InvalidMagic,
+    #[error("Unsupported recording version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Recording declares {expected} frames but contains {actual}")]
+    FrameCountMismatch { expected: u32, actual: u32 },
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(value: io::Error) -> Self {
+        RecordingError::Io(value)
+    }
+}
+
+/// Writes the frame count into the header on `finish`, or on drop when the
+/// run ends early on an error, so a recording of a crash can be replayed.
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    frame_count: u32,
+    finished: bool,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path, seed: i16) -> Result<Self, RecordingError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+        writer.write_i16::<BigEndian>(seed)?;
+        writer.write_u32::<BigEndian>(0)?;
+        Ok(Self {
+            writer,
+            frame_count: 0,
+            finished: false,
+        })
+    }
+
+    pub fn record(&mut self, input: InputState) -> Result<(), RecordingError> {
+        self.writer.write_u8(input.to_bits())?;
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), RecordingError> {
+        self.write_frame_count()
+    }
+
+    fn write_frame_count(&mut self) -> Result<(), RecordingError> {
+        self.finished = true;
+        self.writer.seek(SeekFrom::Start(FRAME_COUNT_OFFSET))?;
+        self.writer.write_u32::<BigEndian>(self.frame_count)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for InputRecorder {
+    fn drop(&mut self) {
+        if !self.finished
+            && let Err(e) = self.write_frame_count()
+        {
+            warn!("Could not finish the input recording: {e:?}");
+        }
+    }
+}
+
+pub struct InputPlayer {
+    seed: i16,
+    frames: Vec<u8>,
+    next_frame: usize,
+}
+
+impl InputPlayer {
+    pub fn open(path: &Path) -> Result<Self, RecordingError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(RecordingError::InvalidMagic);
+        }
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+        let seed = reader.read_i16::<BigEndian>()?;
+        let expected = reader.read_u32::<BigEndian>()?;
+
+        let mut frames = Vec::with_capacity(expected as usize);
+        reader.read_to_end(&mut frames)?;
+        let actual = frames.len() as u32;
+        if actual != expected {
+            return Err(RecordingError::FrameCountMismatch { expected, actual });
+        }
+
+        Ok(Self {
+            seed,
+            frames,
+            next_frame: 0,
+        })
+    }
+
+    pub fn seed(&self) -> i16 {
+        self.seed
+    }
+
+    pub fn next_input(&mut self) -> Option<InputState> {
+        let bits = self.frames.get(self.next_frame)?;
+        self.next_frame += 1;
+        Some(InputState::from_bits(*bits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn dropped_recorder_can_be_replayed() {
+        let path = env::temp_dir().join(format!("arw-recording-{}.arwi", std::process::id()));
+        let mut recorder = InputRecorder::create(&path, 42).unwrap();
+        for _ in 0..3 {
+            recorder.record(InputState::default()).unwrap();
+        }
+        drop(recorder);
+
+        let player = InputPlayer::open(&path);
+        fs::remove_file(&path).unwrap();
+        let mut player = player.unwrap();
+        assert_eq!(player.seed(), 42);
+        assert_eq!((0..4).filter_map(|_| player.next_input()).count(), 3);
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use log::info;
+
+const REPORT_INTERVAL_FRAMES: u32 = 50;
+
+#[derive(Default)]
+struct Counter {
+    total: Duration,
+    calls: u32,
+}
+
+impl Counter {
+    fn record(&mut self, elapsed: Duration) {
+        self.total += elapsed;
+        self.calls += 1;
+    }
+
+    fn per_frame(&self, frames: u32) -> (Duration, u32) {
+        (self.total / frames, self.calls / frames)
+    }
+}
+
+/// Time spent rasterizing, accumulated over a few frames and logged as
+/// per-frame averages. Line time is also part of the polygon time.
+#[derive(Default)]
+pub struct RenderProfile {
+    fill_polygon: Counter,
+    draw_line: Counter,
+    frames: u32,
+}
+
+impl RenderProfile {
+    pub fn record_fill_polygon(&mut self, elapsed: Duration) {
+        self.fill_polygon.record(elapsed);
+    }
+
+    pub fn record_draw_line(&mut self, elapsed: Duration) {
+        self.draw_line.record(elapsed);
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        if self.frames < REPORT_INTERVAL_FRAMES {
+            return;
+        }
+        let (fill_time, fill_calls) = self.fill_polygon.per_frame(self.frames);
+        let (line_time, line_calls) = self.draw_line.per_frame(self.frames);
+        info!(
+            "per frame: fill_polygon {fill_calls} calls {fill_time:?}, draw_line {line_calls} calls {line_time:?}"
+        );
+        *self = Self::default();
+    }
+}
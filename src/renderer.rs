@@ -1,28 +1,47 @@
 use std::{
-    io::{self, Cursor},
+    cmp::{max, min},
+    io::{self, Read},
     num::NonZeroU32,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
 };
 
-use byteorder::{BigEndian, ReadBytesExt};
+use clap::ValueEnum;
+use log::warn;
+use serde::Deserialize;
 use softbuffer::{Context, SoftBufferError, Surface};
 use thiserror::Error;
-use winit::window::Window;
+use winit::{dpi::PhysicalSize, window::Window};
 
-const SCALE_FACTOR: usize = 3;
+use crate::color_vision::ColorVision;
+
+pub const DEFAULT_SCALE: u32 = 3;
+const FALLBACK_REFRESH_MILLIHERTZ: u32 = 60_000;
 pub const SCREEN_W: usize = 320;
 pub const SCREEN_H: usize = 200;
-pub const SCALED_H: usize = SCREEN_H * SCALE_FACTOR;
-pub const SCALED_W: usize = SCREEN_W * SCALE_FACTOR;
-const NUM_COLORS: usize = 16;
+pub const NUM_COLORS: usize = 16;
+pub const PALETTE_SIZE: usize = NUM_COLORS * 2;
+pub const ACT_PALETTE_SIZE: usize = NUM_COLORS * 3;
+const SCANLINE_BRIGHTNESS: u32 = 160;
+/// Edge masks hold one byte per pixel: 0 off polygon edges, otherwise how
+/// much of the pixel the polygon covers in `EDGE_COVERAGE` units, with
+/// `EDGE_RIGHT` set when the uncovered side is the next pixel.
+pub const EDGE_COVERAGE: u8 = 0x7F;
+pub const EDGE_RIGHT: u8 = 0x80;
 
 #[derive(Error, Debug)]
 pub enum RendererError {
     #[error("Error in the underlying stream")]
-    Io(io::Error),
+    Io(#[source] io::Error),
     #[error("Error during softbuffer creation")]
-    Softbuffer(SoftBufferError),
+    Softbuffer(#[source] SoftBufferError),
     #[error("Impossible resize surface")]
     SurfaceResize,
+    #[error("Palette data too short: {0} bytes")]
+    InvalidPalette(usize),
+    #[error("Error while encoding a PNG")]
+    Png(#[source] png::EncodingError),
 }
 
 impl From<io::Error> for RendererError {
@@ -31,74 +50,507 @@ impl From<io::Error> for RendererError {
     }
 }
 
+impl From<png::EncodingError> for RendererError {
+    fn from(value: png::EncodingError) -> Self {
+        RendererError::Png(value)
+    }
+}
+
 impl From<SoftBufferError> for RendererError {
     fn from(value: SoftBufferError) -> Self {
         RendererError::Softbuffer(value)
     }
 }
 
-pub struct Renderer {
-    window: Window,
+const DEBUG_PALETTE: [u32; NUM_COLORS] = [
+    0x000000, 0xFFFFFF, 0xFF0000, 0x00FF00, 0x0000FF, 0xFFFF00, 0xFF00FF, 0x00FFFF, 0x800000,
+    0x008000, 0x000080, 0x808000, 0x800080, 0x008080, 0xFF8000, 0x808080,
+];
+
+/// Expands the 16 big-endian 0x0RGB words of a game palette to 0xRRGGBB.
+/// Missing trailing entries are left black.
+pub fn decode_palette(bytes: &[u8]) -> [u32; NUM_COLORS] {
+    let mut palette = [0; NUM_COLORS];
+    for (color, word) in palette.iter_mut().zip(bytes.chunks_exact(2)) {
+        let color444 = u16::from_be_bytes([word[0], word[1]]);
+        let mut r = (color444 & 0x0F00) >> 8;
+        let mut g = (color444 & 0xF0) >> 4;
+        let mut b = color444 & 0x0F;
+        r |= r << 4;
+        g |= g << 4;
+        b |= b << 4;
+        *color = (u32::from(r) << 16) | (u32::from(g) << 8) | b as u32;
+    }
+    palette
+}
+
+pub fn read_palette<R: Read + ?Sized>(reader: &mut R) -> Result<[u32; NUM_COLORS], RendererError> {
+    let mut bytes = [0; PALETTE_SIZE];
+    reader.read_exact(&mut bytes)?;
+    Ok(decode_palette(&bytes))
+}
+
+pub fn palette_to_act(palette: &[u32; NUM_COLORS]) -> [u8; ACT_PALETTE_SIZE] {
+    let mut act = [0; ACT_PALETTE_SIZE];
+    for (rgb, &color) in act.chunks_exact_mut(3).zip(palette.iter()) {
+        rgb.copy_from_slice(&color.to_be_bytes()[1..]);
+    }
+    act
+}
+
+pub fn act_to_palette(data: &[u8]) -> Result<[u32; NUM_COLORS], RendererError> {
+    if data.len() < ACT_PALETTE_SIZE {
+        return Err(RendererError::InvalidPalette(data.len()));
+    }
+    let mut palette = [0; NUM_COLORS];
+    for (color, rgb) in palette.iter_mut().zip(data.chunks_exact(3)) {
+        *color = u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]);
+    }
+    Ok(palette)
+}
+
+/// Expands a page, two pixels per byte with the high nibble on the
+/// left, to one `0x00RRGGBB` value per pixel.
+pub fn indexed_to_rgb(src: &[u8], palette: &[u32; NUM_COLORS]) -> Vec<u32> {
+    src.iter()
+        .flat_map(|&two_pixels_byte| [two_pixels_byte >> 4, two_pixels_byte & 0x0F])
+        .map(|index| palette[index as usize])
+        .collect()
+}
+
+/// Reduces a page drawn at `scale` times 320x200 to the original resolution
+/// by keeping the top left pixel of every block.
+pub fn downscale(src: &[u8], scale: usize) -> Vec<u8> {
+    let stride = SCREEN_W * scale / 2;
+    let pixel = |x: usize, y: usize| {
+        let pixel_pair = src[y * scale * stride + x * scale / 2];
+        if (x * scale) & 1 == 0 {
+            pixel_pair >> 4
+        } else {
+            pixel_pair & 0x0F
+        }
+    };
+    (0..SCREEN_H)
+        .flat_map(|y| (0..SCREEN_W / 2).map(move |x| (x * 2, y)))
+        .map(|(x, y)| (pixel(x, y) << 4) | pixel(x + 1, y))
+        .collect()
+}
+
+pub trait Renderer {
+    fn set_palette(&mut self, reader: &mut dyn Read) -> Result<(), RendererError>;
+    fn update_display(&mut self, src: &[u8]) -> Result<(), RendererError>;
+    fn palette(&self) -> [u32; NUM_COLORS];
+    fn import_palette(&mut self, data: &[u8]) -> Result<(), RendererError>;
+    fn set_debug_palette(&mut self, _enabled: bool) {}
+    fn set_color_vision(&mut self, _color_vision: ColorVision) {}
+    fn set_title(&self, _title: &str) {}
+    /// Number of presented frames a palette change blends over, 0 for an
+    /// instant switch.
+    fn set_palette_fade(&mut self, _frames: usize) {}
+    /// Edge mask of the page presented next, see `EDGE_COVERAGE`.
+    fn set_edges(&mut self, _edges: &[u8]) {}
+
+    /// Presents a page drawn at `scale` times the original resolution.
+    /// Renderers that only handle 320x200 get it downscaled.
+    fn update_display_scaled(&mut self, src: &[u8], scale: usize) -> Result<(), RendererError> {
+        if scale == 1 {
+            self.update_display(src)
+        } else {
+            self.update_display(&downscale(src, scale))
+        }
+    }
+
+    fn export_palette(&self) -> [u8; ACT_PALETTE_SIZE] {
+        palette_to_act(&self.palette())
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    None,
+    Crt,
+}
+
+/// `Original` stretches the 320x200 picture to 320x240 like the non-square
+/// pixels of a 4:3 display, `Square` keeps one row per scaled line.
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Aspect {
+    Original,
+    #[default]
+    Square,
+}
+
+impl Aspect {
+    pub fn display_height(self) -> usize {
+        match self {
+            Aspect::Original => SCREEN_H * 6 / 5,
+            Aspect::Square => SCREEN_H,
+        }
+    }
+
+    /// Integer scale and top-left offset of the picture centred in a
+    /// `width`x`height` surface.
+    pub fn layout(self, width: usize, height: usize) -> (usize, usize, usize) {
+        let display_h = self.display_height();
+        let scale = max(1, min(width / SCREEN_W, height / display_h));
+        (
+            scale,
+            width.saturating_sub(SCREEN_W * scale) / 2,
+            height.saturating_sub(display_h * scale) / 2,
+        )
+    }
+
+    /// Maps a position in a `width`x`height` surface back to the source pixel
+    /// drawn there, if any.
+    pub fn screen_position(
+        self,
+        width: usize,
+        height: usize,
+        x: f64,
+        y: f64,
+    ) -> Option<(usize, usize)> {
+        let (scale, offset_x, offset_y) = self.layout(width, height);
+        let (x, y) = (x - offset_x as f64, y - offset_y as f64);
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let screen_x = x as usize / scale;
+        let screen_y = y as usize * SCREEN_H / (self.display_height() * scale);
+        (screen_x < SCREEN_W && screen_y < SCREEN_H).then_some((screen_x, screen_y))
+    }
+
+    pub fn window_size(self, scale: u32) -> (u32, u32) {
+        (
+            SCREEN_W as u32 * scale,
+            self.display_height() as u32 * scale,
+        )
+    }
+}
+
+type WindowSurface = Surface<Rc<Window>, Rc<Window>>;
+
+pub struct SoftbufferRenderer {
+    window: Rc<Window>,
+    /// Kept across frames, and only rebuilt after it failed.
+    surface: Option<WindowSurface>,
+    /// Scale the window fell back to after a surface at its size could not
+    /// be allocated. Later frames stay within it.
+    fallback_scale: Option<u32>,
     palette: [u32; NUM_COLORS],
+    debug_palette: bool,
+    filter: Filter,
+    aspect: Aspect,
+    color_vision: ColorVision,
+    vsync_interval: Option<Duration>,
+    last_present: Instant,
+    antialias: bool,
+    edges: Vec<u8>,
+    palette_fade: usize,
+    fade_from: [u32; NUM_COLORS],
+    fade_frame: usize,
 }
 
-impl Renderer {
-    pub fn new(window: Window) -> Self {
+impl SoftbufferRenderer {
+    /// softbuffer has no access to the vertical blank, so `vsync` can only
+    /// space presents one monitor refresh apart: it avoids presenting more
+    /// often than the display can show, but does not prevent tearing. Without
+    /// it presents are free-running, paced only by the engine frame cap.
+    ///
+    /// `antialias` blends the pixels on polygon span ends with their outside
+    /// neighbour, by the coverage the rasterizer recorded for them. The pages
+    /// only hold 16 indices, so blending there would have to snap back to the
+    /// nearest palette entry; working on resolved RGB keeps the pages and the
+    /// VM untouched and gives real intermediate colors.
+    pub fn new(
+        window: Window,
+        filter: Filter,
+        aspect: Aspect,
+        vsync: bool,
+        antialias: bool,
+    ) -> Self {
+        let vsync_interval = vsync.then(|| Self::refresh_interval(&window));
         Self {
-            window,
+            window: Rc::new(window),
+            surface: None,
+            fallback_scale: None,
             palette: Default::default(),
+            debug_palette: false,
+            filter,
+            aspect,
+            color_vision: ColorVision::default(),
+            vsync_interval,
+            last_present: Instant::now(),
+            antialias,
+            edges: Vec::new(),
+            palette_fade: 0,
+            fade_from: Default::default(),
+            fade_frame: 0,
         }
     }
 
-    pub fn set_palette(&mut self, cursor: &mut Cursor<Vec<u8>>) -> Result<(), RendererError> {
-        for i in 0..NUM_COLORS {
-            let color444 = cursor.read_u16::<BigEndian>()?;
-            let mut r = (color444 & 0x0F00) >> 8;
-            let mut g = (color444 & 0xF0) >> 4;
-            let mut b = color444 & 0x0F;
-            r |= r << 4;
-            g |= g << 4;
-            b |= b << 4;
-            self.palette[i] = (u32::from(r) << 16) | (u32::from(g) << 8) | b as u32;
+    fn refresh_interval(window: &Window) -> Duration {
+        let millihertz = window
+            .current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .filter(|&millihertz| millihertz > 0)
+            .unwrap_or(FALLBACK_REFRESH_MILLIHERTZ);
+        Duration::from_secs_f64(1000.0 / millihertz as f64)
+    }
+
+    fn channel(rgb: u32, shift: u32) -> u32 {
+        (rgb >> shift) & 0xFF
+    }
+
+    fn darken(rgb: u32) -> u32 {
+        [16, 8, 0].iter().fold(0, |acc, &shift| {
+            acc | (Self::channel(rgb, shift) * SCANLINE_BRIGHTNESS / 255) << shift
+        })
+    }
+
+    fn bleed(line: &mut [u32]) {
+        let mut previous = line.first().copied().unwrap_or_default();
+        for pixel in line.iter_mut() {
+            let current = *pixel;
+            *pixel = [16, 8, 0].iter().fold(0, |acc, &shift| {
+                let mixed =
+                    (Self::channel(current, shift) * 3 + Self::channel(previous, shift)) / 4;
+                acc | mixed << shift
+            });
+            previous = current;
+        }
+    }
+
+    /// Mixes each pixel on a polygon edge with its neighbour outside the
+    /// polygon, weighted by how much of the pixel the polygon covers. Pixels
+    /// off the edges, text and flat fills included, are left untouched.
+    fn blend_edges(rgb: &mut [u32], edges: &[u8], width: usize) {
+        let source = rgb.to_vec();
+        for (i, &edge) in edges.iter().enumerate() {
+            if edge == 0 {
+                continue;
+            }
+            let x = i % width;
+            let outside = match edge & EDGE_RIGHT {
+                0 if x > 0 => i - 1,
+                EDGE_RIGHT if x + 1 < width => i + 1,
+                _ => continue,
+            };
+            let coverage = u32::from(edge & EDGE_COVERAGE);
+            let (inside, outside) = (source[i], source[outside]);
+            rgb[i] = [16, 8, 0].iter().fold(0, |acc, &shift| {
+                let mixed = (Self::channel(inside, shift) * coverage
+                    + Self::channel(outside, shift) * (u32::from(EDGE_COVERAGE) - coverage))
+                    / u32::from(EDGE_COVERAGE);
+                acc | mixed << shift
+            });
         }
-        Ok(())
     }
 
-    pub fn update_display(&mut self, src: &[u8]) -> Result<(), RendererError> {
-        let context = Context::new(&self.window).unwrap();
+    fn draw(&mut self, src: &[u8], internal_scale: usize) -> Result<(), RendererError> {
+        let mut surface = match self.surface.take() {
+            Some(surface) => surface,
+            None => {
+                let context = Context::new(Rc::clone(&self.window))?;
+                Surface::new(&context, Rc::clone(&self.window))?
+            }
+        };
+        let result = self.draw_into(&mut surface, src, internal_scale);
+        self.surface = Some(surface);
+        result
+    }
 
-        let mut surface = Surface::new(&context, &self.window).unwrap();
+    fn draw_into(
+        &mut self,
+        surface: &mut WindowSurface,
+        src: &[u8],
+        internal_scale: usize,
+    ) -> Result<(), RendererError> {
+        let (src_w, src_h) = (SCREEN_W * internal_scale, SCREEN_H * internal_scale);
         let size = self.window.inner_size();
-        let (Some(width), Some(height)) =
-            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
-        else {
-            return Err(RendererError::SurfaceResize);
+        let (mut width, mut height) = match self.fallback_scale {
+            Some(scale) => {
+                let (max_w, max_h) = self.aspect.window_size(scale);
+                (min(size.width, max_w), min(size.height, max_h))
+            }
+            None => (size.width, size.height),
         };
-        surface.resize(width, height)?;
+        loop {
+            let (Some(surface_w), Some(surface_h)) =
+                (NonZeroU32::new(width), NonZeroU32::new(height))
+            else {
+                return Err(RendererError::SurfaceResize);
+            };
+            match surface.resize(surface_w, surface_h) {
+                Ok(()) => break,
+                Err(e) => {
+                    let scale = width / SCREEN_W as u32;
+                    if scale <= 1 {
+                        return Err(e.into());
+                    }
+                    self.fallback_scale = Some(scale / 2);
+                    (width, height) = self.aspect.window_size(scale / 2);
+                    warn!(
+                        "Unable to allocate a {}x{} surface, falling back to scale {}",
+                        surface_w,
+                        surface_h,
+                        scale / 2
+                    );
+                    let _ = self
+                        .window
+                        .request_inner_size(PhysicalSize::new(width, height));
+                }
+            }
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        let (scale, offset_x, offset_y) = self.aspect.layout(width, height);
+        let scaled_w = SCREEN_W * scale;
+        let scaled_h = self.aspect.display_height() * scale;
+        let visible_w = min(scaled_w, width);
 
         let mut dest = surface.buffer_mut()?;
-        let src_lines = src.chunks_exact(SCREEN_W / 2);
-        let dest_lines = dest.chunks_exact_mut(SCALED_W * SCALE_FACTOR);
-
-        for (src_line, dest_line) in src_lines.zip(dest_lines) {
-            for (i, &two_pixels_byte) in src_line.iter().enumerate() {
-                let left_pixel_index = (two_pixels_byte >> 4) as usize;
-                let right_pixel_index = (two_pixels_byte & 0x0F) as usize;
-
-                let left_color = self.palette[left_pixel_index];
-                let right_color = self.palette[right_pixel_index];
-
-                for y in 0..SCALE_FACTOR {
-                    for x in 0..SCALE_FACTOR {
-                        let current_row_idx = SCALED_W * y;
-                        let curr_col_idx = i * 2 * SCALE_FACTOR;
-                        dest_line[current_row_idx + curr_col_idx + x] = left_color;
-                        dest_line[current_row_idx + curr_col_idx + SCALE_FACTOR + x] = right_color;
-                    }
+        dest.fill(0);
+        let rgb = self.resolve(src, src_w);
+        let crt = self.filter == Filter::Crt;
+        let mut scaled_line = vec![0; scaled_w];
+        let mut scanline = vec![0; scaled_w];
+        for (y, src_line) in rgb.chunks_exact(src_w).enumerate() {
+            let first_row = y * scaled_h / src_h;
+            let rows = (y + 1) * scaled_h / src_h - first_row;
+            if rows == 0 {
+                continue;
+            }
+            for (x, color) in scaled_line.iter_mut().enumerate() {
+                *color = src_line[x * src_w / scaled_w];
+            }
+            if crt {
+                Self::bleed(&mut scaled_line);
+                for (dark, &color) in scanline.iter_mut().zip(scaled_line.iter()) {
+                    *dark = Self::darken(color);
                 }
             }
+
+            for row in 0..rows {
+                let dest_y = offset_y + first_row + row;
+                if dest_y >= height {
+                    break;
+                }
+                let line = if crt && rows > 1 && row == rows - 1 {
+                    &scanline
+                } else {
+                    &scaled_line
+                };
+                let dest_start = dest_y * width + offset_x;
+                dest[dest_start..dest_start + visible_w].copy_from_slice(&line[..visible_w]);
+            }
+        }
+        if let Some(interval) = self.vsync_interval {
+            thread::sleep(interval.saturating_sub(self.last_present.elapsed()));
         }
         dest.present()?;
+        self.last_present = Instant::now();
+        self.fade_frame = min(self.fade_frame + 1, self.palette_fade);
+        Ok(())
+    }
+
+    /// The palette on screen, partway from `fade_from` to `palette` while a
+    /// fade is running.
+    fn faded_palette(&self) -> [u32; NUM_COLORS] {
+        if self.fade_frame >= self.palette_fade {
+            return self.palette;
+        }
+        let (done, total) = (self.fade_frame as u32, self.palette_fade as u32);
+        std::array::from_fn(|color| {
+            [16, 8, 0].iter().fold(0, |acc, &shift| {
+                let from = Self::channel(self.fade_from[color], shift);
+                let to = Self::channel(self.palette[color], shift);
+                acc | ((from * (total - done) + to * done) / total) << shift
+            })
+        })
+    }
+
+    fn resolve(&self, src: &[u8], width: usize) -> Vec<u32> {
+        let mut rgb = indexed_to_rgb(src, &self.active_palette());
+        if self.antialias && self.edges.len() == rgb.len() {
+            Self::blend_edges(&mut rgb, &self.edges, width);
+        }
+        rgb
+    }
+
+    fn active_palette(&self) -> [u32; NUM_COLORS] {
+        if self.debug_palette {
+            DEBUG_PALETTE
+        } else {
+            self.faded_palette()
+        }
+    }
+}
+
+impl Renderer for SoftbufferRenderer {
+    fn set_palette(&mut self, reader: &mut dyn Read) -> Result<(), RendererError> {
+        let palette = self.color_vision.apply(read_palette(reader)?);
+        if palette != self.palette {
+            self.fade_from = self.faded_palette();
+            self.fade_frame = 0;
+        }
+        self.palette = palette;
         Ok(())
     }
+
+    fn update_display(&mut self, src: &[u8]) -> Result<(), RendererError> {
+        self.update_display_scaled(src, 1)
+    }
+
+    /// Higher resolution pages are sampled straight into the window instead
+    /// of going through a 320x200 copy. A surface that fails, e.g. after a
+    /// GPU reset, is dropped and recreated once before giving up.
+    fn update_display_scaled(
+        &mut self,
+        src: &[u8],
+        internal_scale: usize,
+    ) -> Result<(), RendererError> {
+        match self.draw(src, internal_scale) {
+            Err(RendererError::Softbuffer(e)) => {
+                warn!("Display surface failed ({e}), recreating it");
+                self.surface = None;
+                self.draw(src, internal_scale)
+            }
+            result => result,
+        }
+    }
+
+    fn palette(&self) -> [u32; NUM_COLORS] {
+        self.palette
+    }
+
+    fn import_palette(&mut self, data: &[u8]) -> Result<(), RendererError> {
+        self.palette = act_to_palette(data)?;
+        Ok(())
+    }
+
+    fn set_debug_palette(&mut self, enabled: bool) {
+        self.debug_palette = enabled;
+    }
+
+    fn set_color_vision(&mut self, color_vision: ColorVision) {
+        self.color_vision = color_vision;
+    }
+
+    fn set_title(&self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn set_palette_fade(&mut self, frames: usize) {
+        self.palette_fade = frames;
+    }
+
+    fn set_edges(&mut self, edges: &[u8]) {
+        if self.antialias {
+            self.edges.clear();
+            self.edges.extend_from_slice(edges);
+        }
+    }
 }
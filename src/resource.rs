@@ -1,14 +1,18 @@
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{self, BufReader},
-    path::PathBuf,
+    fs,
+    io::{self, Cursor, ErrorKind},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
 };
 
+use log::{debug, info};
+
 use crate::{
-    bank::{BankError, BankReader},
+    bank::{BankError, BankReader, BankSource, DirectoryBankSource, find_file},
     loaded::{LoadedPart, LoadedPartError},
-    mem_entry::{MemEntry, MemEntryError},
+    mem_entry::{MemEntry, MemEntryError, MemListFormat},
     parts::{GamePart, SEGMENT_IDX_BY_PART, Segment},
 };
 use thiserror::Error;
@@ -16,13 +20,19 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum ResourceError {
     #[error("Error opening memlist file")]
-    MemListOpen(io::Error),
+    MemListOpen(#[source] io::Error),
     #[error("Error while processing bank data")]
-    BankError(BankError),
+    BankError(#[source] BankError),
     #[error("Error while creating MemEntry")]
-    MemEntryError(MemEntryError),
+    MemEntryError(#[source] MemEntryError),
     #[error("Error while loading game part")]
-    LoadedPartError(LoadedPartError),
+    LoadedPartError(#[source] LoadedPartError),
+    #[error("No memlist entry at index {0}")]
+    NoSuchEntry(usize),
+    #[error("{0} is not available in this data set")]
+    UnsupportedPart(GamePart),
+    #[error("Error reading override file {0}")]
+    OverrideRead(PathBuf, #[source] io::Error),
 }
 
 impl From<MemEntryError> for ResourceError {
@@ -44,63 +54,288 @@ impl From<LoadedPartError> for ResourceError {
 }
 
 pub const NUM_MEM_ENTRIES: u16 = 146;
+pub const DEFAULT_MEM_LIST_FILE: &str = "memlist.bin";
+
+pub fn has_data_set(dir: &Path, mem_list_file: &str) -> bool {
+    find_file(dir, mem_list_file).is_file()
+}
+
+/// Returns the first candidate directory holding a data set.
+pub fn find_data_dir<'a>(candidates: &'a [PathBuf], mem_list_file: &str) -> Option<&'a PathBuf> {
+    candidates
+        .iter()
+        .find(|dir| has_data_set(dir, mem_list_file))
+}
+
+const PART_SEGMENTS: [Segment; 4] = [
+    Segment::Palette,
+    Segment::Bytecode,
+    Segment::PolyCinematic,
+    Segment::Polygon,
+];
+
+type SegmentData = HashMap<Segment, Vec<u8>>;
+
+/// Segments of a part being read on a background thread.
+struct Prefetch {
+    part: GamePart,
+    receiver: Receiver<Result<SegmentData, BankError>>,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum DataSet {
+    Full,
+    Demo,
+}
 
-#[derive(Default)]
 pub struct ResourceRegistry {
-    data_dir: PathBuf,
+    bank_source: Box<dyn BankSource>,
+    prefetch: Option<Prefetch>,
+    mem_list_format: MemListFormat,
+    override_dir: Option<PathBuf>,
     pub mem_list: Vec<MemEntry>,
 }
 
 impl ResourceRegistry {
-    pub fn new(data_dir: PathBuf) -> Self {
+    pub fn new(data_dir: PathBuf, mem_list_file: &str) -> Self {
+        Self::with_bank_source(Box::new(DirectoryBankSource::with_mem_list(
+            &data_dir,
+            mem_list_file,
+        )))
+    }
+
+    pub fn with_bank_source(bank_source: Box<dyn BankSource>) -> Self {
         Self {
-            data_dir,
+            bank_source,
+            prefetch: None,
+            mem_list_format: MemListFormat::default(),
+            override_dir: None,
             mem_list: Vec::with_capacity(NUM_MEM_ENTRIES as usize),
         }
     }
 
+    /// A directory of loose, unpacked resources named by entry index, like
+    /// `0x15.bin`, loaded in place of the bank data.
+    pub fn set_override_dir(&mut self, override_dir: Option<PathBuf>) {
+        self.override_dir = override_dir;
+        self.prefetch = None;
+    }
+
+    fn override_path(&self, index: usize) -> Option<PathBuf> {
+        let dir = self.override_dir.as_deref()?;
+        Some(find_file(dir, &format!("{index:#04x}.bin"))).filter(|path| path.is_file())
+    }
+
+    /// Takes effect on the next `read_entries`.
+    pub fn set_mem_list_format(&mut self, mem_list_format: MemListFormat) {
+        self.mem_list_format = mem_list_format;
+    }
+
     pub fn read_entries(&mut self) -> Result<(), ResourceError> {
-        let file_path = self.data_dir.join("memlist.bin");
-        let file = File::open(file_path).map_err(ResourceError::MemListOpen)?;
-        let mut reader = BufReader::new(file);
+        let data = self
+            .bank_source
+            .read_mem_list()
+            .map_err(ResourceError::MemListOpen)?;
+        let mut reader = Cursor::new(data);
 
-        for _ in 0..NUM_MEM_ENTRIES {
-            let mem_entry = MemEntry::from_reader(&mut reader)?;
-            self.mem_list.push(mem_entry);
+        let mut mem_list = Vec::with_capacity(NUM_MEM_ENTRIES as usize);
+        loop {
+            match MemEntry::from_reader(&mut reader, self.mem_list_format) {
+                Ok(mem_entry) if mem_entry.is_end_of_list() => break,
+                Ok(mem_entry) => mem_list.push(mem_entry),
+                Err(MemEntryError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
         }
 
+        self.mem_list = mem_list;
+        self.prefetch = None;
         Ok(())
     }
 
+    pub fn entry_count(&self) -> usize {
+        self.mem_list.len()
+    }
+
+    pub fn total_unpacked_size(&self) -> usize {
+        self.mem_list.iter().map(|entry| entry.size as usize).sum()
+    }
+
+    pub fn data_set(&self) -> DataSet {
+        if self.mem_list.len() < NUM_MEM_ENTRIES as usize {
+            DataSet::Demo
+        } else {
+            DataSet::Full
+        }
+    }
+
+    pub fn is_part_available(&self, game_part: GamePart) -> bool {
+        let part_idx = game_part as usize - GamePart::One as usize;
+        SEGMENT_IDX_BY_PART[part_idx]
+            .iter()
+            .all(|&idx| idx < self.mem_list.len())
+    }
+
     pub fn load_entry(&mut self, index: usize) -> Result<Vec<u8>, ResourceError> {
-        let entry = &mut self.mem_list[index];
-        Ok(BankReader::read_bank(&self.data_dir, entry)?)
+        if let Some(path) = self.override_path(index) {
+            info!("Loading entry {index:#04X} from {}", path.display());
+            return fs::read(&path).map_err(|e| ResourceError::OverrideRead(path, e));
+        }
+        let entry = self
+            .mem_list
+            .get(index)
+            .ok_or(ResourceError::NoSuchEntry(index))?;
+        Ok(BankReader::read_bank(self.bank_source.as_mut(), entry)?)
+    }
+
+    pub fn check_entry(&self, index: usize) -> Result<(), ResourceError> {
+        let entry = self
+            .mem_list
+            .get(index)
+            .ok_or(ResourceError::NoSuchEntry(index))?;
+        Ok(BankReader::check_bank(self.bank_source.as_ref(), entry)?)
+    }
+
+    pub fn segment_indices(game_part: GamePart) -> [usize; 4] {
+        SEGMENT_IDX_BY_PART[game_part as usize - GamePart::One as usize]
+    }
+
+    fn part_entries(game_part: GamePart) -> impl Iterator<Item = (Segment, usize)> {
+        let indices = ResourceRegistry::segment_indices(game_part);
+        PART_SEGMENTS
+            .into_iter()
+            .map(move |segment| (segment, indices[segment as usize]))
+            .filter(|(_, idx)| *idx != 0)
+    }
+
+    /// Starts reading the segments of `game_part` on a background thread so
+    /// that a later `setup_part` for it does not have to hit the banks.
+    /// Replaces any prefetch still pending.
+    pub fn prefetch_part(&mut self, game_part: GamePart) {
+        if !self.is_part_available(game_part) {
+            return;
+        }
+        // Overrides are read on the spot by `load_entry`.
+        if Self::part_entries(game_part).any(|(_, idx)| self.override_path(idx).is_some()) {
+            return;
+        }
+        let Some(mut source) = self.bank_source.detached() else {
+            return;
+        };
+        let entries: Vec<(Segment, MemEntry)> = Self::part_entries(game_part)
+            .map(|(segment, idx)| (segment, self.mem_list[idx].clone()))
+            .collect();
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let segment_data = entries
+                .into_iter()
+                .map(|(segment, entry)| {
+                    BankReader::read_bank(source.as_mut(), &entry).map(|data| (segment, data))
+                })
+                .collect();
+            // The registry may have dropped the prefetch in the meantime.
+            let _ = sender.send(segment_data);
+        });
+        self.prefetch = Some(Prefetch {
+            part: game_part,
+            receiver,
+        });
+    }
+
+    fn take_prefetched(&mut self, game_part: GamePart) -> Option<SegmentData> {
+        let prefetch = self
+            .prefetch
+            .take()
+            .filter(|prefetch| prefetch.part == game_part)?;
+        match prefetch.receiver.recv() {
+            Ok(Ok(segment_data)) => Some(segment_data),
+            Ok(Err(e)) => {
+                debug!("Prefetch of {game_part} failed, loading it again: {e:?}");
+                None
+            }
+            Err(_) => None,
+        }
     }
 
     pub fn setup_part(&mut self, game_part: GamePart) -> Result<LoadedPart, ResourceError> {
-        let part_idx = game_part as usize - GamePart::One as usize;
+        if !self.is_part_available(game_part) {
+            return Err(ResourceError::UnsupportedPart(game_part));
+        }
+        if let Some(segment_data) = self.take_prefetched(game_part) {
+            return Ok(LoadedPart::from(segment_data)?);
+        }
 
-        let segment_data: HashMap<Segment, Vec<u8>> = [
-            Segment::Palette,
-            Segment::Bytecode,
-            Segment::PolyCinematic,
-            Segment::Polygon,
-        ]
-        .map(|segment| (segment, SEGMENT_IDX_BY_PART[part_idx][segment as usize]))
-        .into_iter()
-        .filter(|(_, idx)| *idx != 0)
-        .try_fold(
+        let segment_data = Self::part_entries(game_part).try_fold(
             HashMap::new(),
-            |mut map, (segment, idx)| -> Result<HashMap<Segment, Vec<u8>>, ResourceError> {
+            |mut map, (segment, idx)| -> Result<SegmentData, ResourceError> {
                 map.insert(segment, self.load_entry(idx)?);
                 Ok(map)
             },
         )?;
-
         Ok(LoadedPart::from(segment_data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::{
+        bank::MemoryBankSource,
+        test_data::{bank_source, short_bank_source},
+    };
+
+    fn registry(source: MemoryBankSource) -> ResourceRegistry {
+        let mut registry = ResourceRegistry::with_bank_source(Box::new(source));
+        registry.read_entries().unwrap();
+        registry
+    }
+
+    #[test]
+    fn parts_have_distinct_segments_except_the_password_screen() {
+        for part in GamePart::iter() {
+            for other in GamePart::iter().filter(|&other| other != part) {
+                // Nine and Ten load the same entries in the reference too.
+                // Otherwise only the polygon segment is shared.
+                let shared = matches!(
+                    (part, other),
+                    (GamePart::Nine, GamePart::Ten) | (GamePart::Ten, GamePart::Nine)
+                );
+                let (indices, other_indices) = (
+                    ResourceRegistry::segment_indices(part),
+                    ResourceRegistry::segment_indices(other),
+                );
+                assert_eq!(
+                    indices[..3] == other_indices[..3],
+                    shared,
+                    "{part:?} {other:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn password_screen_loads_from_full_memlist() {
+        let entries: Vec<_> = [0x7D, 0x7E, 0x7F]
+            .into_iter()
+            .map(|index| (index, vec![index as u8]))
+            .collect();
+        let mut registry = registry(bank_source(&entries));
+        assert!(registry.is_part_available(GamePart::Ten));
+        let part = registry.setup_part(GamePart::Ten).unwrap();
+        assert_eq!(part.segment(Segment::Bytecode), Some(&[0x7E][..]));
+    }
 
-        //if let Some(video_seg) = self.loaded_segments.get(&Segment::Polygon) {
-        //    video.copy_bg(&self.mem_list[*video_seg].data);
-        //}
+    #[test]
+    fn part_past_a_short_memlist_is_unsupported() {
+        let mut registry = registry(short_bank_source(&[], 0x30));
+        assert!(registry.is_part_available(GamePart::Eigth));
+        assert!(!registry.is_part_available(GamePart::Nine));
+        assert!(matches!(
+            registry.setup_part(GamePart::Nine),
+            Err(ResourceError::UnsupportedPart(GamePart::Nine))
+        ));
     }
 }
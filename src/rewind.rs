@@ -0,0 +1,51 @@
+use std::collections::VecDeque;
+
+use crate::{parts::GamePart, video::VideoState, vm::VmState};
+
+pub const DEFAULT_REWIND_FRAMES: usize = 60;
+
+struct RewindFrame {
+    part: GamePart,
+    vm: VmState,
+    video: VideoState,
+}
+
+/// Ring buffer of the states the engine was in at the start of the most
+/// recent frames. States only make sense against the bytecode they were
+/// captured with, so the buffer starts over whenever the part changes.
+pub struct RewindBuffer {
+    capacity: usize,
+    frames: VecDeque<RewindFrame>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, part: GamePart, vm: VmState, video: VideoState) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.frames.back().is_some_and(|frame| frame.part != part) {
+            self.frames.clear();
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(RewindFrame { part, vm, video });
+    }
+
+    /// Takes the most recent state out of the buffer, so repeated calls walk
+    /// further back in time.
+    pub fn pop(&mut self) -> Option<(VmState, VideoState)> {
+        self.frames.pop_back().map(|frame| (frame.vm, frame.video))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+}
@@ -0,0 +1,49 @@
+use std::{fs, io, path::Path};
+
+use thiserror::Error;
+
+use crate::{
+    parts::{GamePart, Segment},
+    resource::{ResourceError, ResourceRegistry},
+};
+
+#[derive(Error, Debug)]
+pub enum SegmentDumpError {
+    #[error("Unable to write the segment")]
+    Io(#[source] io::Error),
+    #[error("Resource registry error")]
+    ResourceError(#[source] ResourceError),
+    #[error("The part has no {0:?} segment")]
+    MissingSegment(Segment),
+}
+
+impl From<io::Error> for SegmentDumpError {
+    fn from(value: io::Error) -> Self {
+        SegmentDumpError::Io(value)
+    }
+}
+
+impl From<ResourceError> for SegmentDumpError {
+    fn from(value: ResourceError) -> Self {
+        SegmentDumpError::ResourceError(value)
+    }
+}
+
+/// Loads `part` and writes its unpacked `segment` to `path` as-is, returning
+/// the number of bytes written.
+pub fn run(
+    data_dir: &Path,
+    mem_list_file: &str,
+    part: GamePart,
+    segment: Segment,
+    path: &Path,
+) -> Result<usize, SegmentDumpError> {
+    let mut resource = ResourceRegistry::new(data_dir.to_path_buf(), mem_list_file);
+    resource.read_entries()?;
+    let loaded_part = resource.setup_part(part)?;
+    let data = loaded_part
+        .segment(segment)
+        .ok_or(SegmentDumpError::MissingSegment(segment))?;
+    fs::write(path, data)?;
+    Ok(data.len())
+}
@@ -0,0 +1,47 @@
+use thiserror::Error;
+
+use crate::bank::{BankError, BankReader};
+
+/// `PACKED` unpacks to `UNPACKED`. It mixes short and long literals with
+/// short and long back-references, and its trailing checksum word only
+/// cancels out if every packed word was read in the right order.
+pub(crate) const PACKED: &[u8] = &[
+    0x78, 0x01, 0x83, 0x34, 0x04, 0x82, 0x72, 0xF2, 0x26, 0x45, 0x44, 0x95, //
+    0x0C, 0x80, 0xA0, 0x6E, 0x80, 0x20, 0xAA, 0x8A, 0x81, 0x3C, 0x98, 0x81, //
+    0x0A, 0x84, 0xB9, 0x25, 0x94, 0x09, 0xD5, 0xE4, 0x94, 0x64, 0x45, 0xC8, //
+    0x00, 0x60, 0x70, 0x80, 0x00, 0x00, 0x00, 0x01, 0x5D, 0xD3, 0x7E, 0xBE, //
+    0x00, 0x00, 0x00, 0x37,
+];
+pub(crate) const UNPACKED: &[u8] = b"ANOTHER WORLD, ANOTHER WORLD. OUT OF THIS WORLD!!!!!!!!";
+
+#[derive(Error, Debug)]
+pub enum SelfTestError {
+    #[error("Unpacking the embedded resource failed")]
+    Bank(#[source] BankError),
+    #[error("Unpacked byte {offset} is {actual:#04X} instead of {expected:#04X}")]
+    OutputMismatch {
+        offset: usize,
+        actual: u8,
+        expected: u8,
+    },
+}
+
+impl From<BankError> for SelfTestError {
+    fn from(value: BankError) -> Self {
+        SelfTestError::Bank(value)
+    }
+}
+
+/// Unpacks the embedded resource and compares it with the known output, so
+/// the bit reader and decode loop can be checked without any game data.
+pub fn run() -> Result<(), SelfTestError> {
+    let data = BankReader::unpack(PACKED, UNPACKED.len())?;
+    match data.iter().zip(UNPACKED).position(|(a, b)| a != b) {
+        Some(offset) => Err(SelfTestError::OutputMismatch {
+            offset,
+            actual: data[offset],
+            expected: UNPACKED[offset],
+        }),
+        None => Ok(()),
+    }
+}
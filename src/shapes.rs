@@ -1,4 +1,4 @@
-use std::io::{self, Cursor};
+use std::io::{self, Read};
 
 use byteorder::ReadBytesExt;
 
@@ -18,12 +18,12 @@ pub struct Polygon {
 }
 
 impl Polygon {
-    pub fn read_vertices(stream: &mut Cursor<Vec<u8>>, zoom: u16) -> Result<Polygon, io::Error> {
+    pub fn read_vertices<R: Read>(stream: &mut R, zoom: u16) -> Result<Polygon, io::Error> {
         let bbw = (stream.read_u8()? as i32 * zoom as i32 / 64) as i16;
         let bbh = (stream.read_u8()? as i32 * zoom as i32 / 64) as i16;
         let num_points = stream.read_u8()? as usize;
 
-        assert!(num_points % 2 == 0, "Points must be even");
+        assert!(num_points.is_multiple_of(2), "Points must be even");
         assert!(num_points < MAX_POINTS, "Points must be max {MAX_POINTS}");
 
         let mut points: Vec<Point> = Vec::with_capacity(num_points);
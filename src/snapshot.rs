@@ -0,0 +1,309 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use num_enum::TryFromPrimitiveError;
+use thiserror::Error;
+
+use crate::{
+    channel::{Channel, ProcessCounter, State},
+    parts::GamePart,
+    video::VideoState,
+    vm::VmState,
+};
+
+const MAGIC: &[u8; 4] = b"ARWS";
+const VERSION: u8 = 3;
+/// Version 1 did not store the running channel.
+const FIRST_VERSION: u8 = 1;
+/// Versions before 3 did not store the palette.
+const PALETTE_VERSION: u8 = 3;
+const NO_PALETTE: u8 = 0xFF;
+const INVALID_PC: u32 = u32::MAX;
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("IO error while accessing the snapshot")]
+    Io(#[source] io::Error),
+    #[error("Not a state snapshot")]
+    InvalidMagic,
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Snapshot refers to unknown game part {0:#06X}")]
+    InvalidPart(u16),
+    #[error("Snapshot selects page {0}, there are only 4")]
+    InvalidPage(u8),
+    #[error("Snapshot holds unknown channel state {0}")]
+    InvalidChannelState(u8),
+    #[error("Snapshot runs channel {0}, there are only 64")]
+    InvalidChannel(u8),
+    #[error("Snapshot uses palette {0}, there are only 32")]
+    InvalidPalette(u8),
+    #[error("Snapshot pages are {actual} bytes but the video uses {expected}")]
+    PageSizeMismatch { expected: usize, actual: usize },
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(value: io::Error) -> Self {
+        SnapshotError::Io(value)
+    }
+}
+
+impl From<TryFromPrimitiveError<GamePart>> for SnapshotError {
+    fn from(value: TryFromPrimitiveError<GamePart>) -> Self {
+        SnapshotError::InvalidPart(value.number)
+    }
+}
+
+/// Everything needed to resume a game between two host frames: the part
+/// whose bytecode the channels point into, the resources loaded on top of
+/// it, and the VM and video state.
+///
+/// | offset | size        | content                                       |
+/// |--------|-------------|-----------------------------------------------|
+/// | 0      | 4           | magic `ARWS`                                  |
+/// | 4      | 1           | format version, 3                             |
+/// | 5      | 2           | game part id, big endian                      |
+/// | 7      | 2 + 2n      | count and ids of the loaded resources         |
+/// |        | 512         | 256 variables, big endian                     |
+/// |        | 64 * 10     | per channel: state, pc, next pc flag, next pc |
+/// |        | 1           | id of the channel that ran last               |
+/// |        | 4           | work, background, front and back page indices |
+/// |        | 1           | palette applied last, `0xFF` for none         |
+/// |        | 4 * (4 + n) | per page: length, big endian, then the pixels |
+///
+/// Program counters are big endian u32 with `0xFFFFFFFF` for none. Version
+/// 1 snapshots, without the running channel, still load with channel 0, and
+/// snapshots before version 3 load without a palette.
+pub struct Snapshot {
+    pub part: GamePart,
+    pub assets: Vec<usize>,
+    pub vm: VmState,
+    pub video: VideoState,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+        writer.write_u16::<BigEndian>(self.part.into())?;
+        writer.write_u16::<BigEndian>(self.assets.len() as u16)?;
+        for &asset in &self.assets {
+            writer.write_u16::<BigEndian>(asset as u16)?;
+        }
+        for &variable in &self.vm.variables {
+            writer.write_i16::<BigEndian>(variable)?;
+        }
+        for channel in &self.vm.channels {
+            Self::write_channel(&mut writer, channel)?;
+        }
+        writer.write_u8(self.vm.running_channel_id as u8)?;
+        let video = &self.video;
+        for buffer in [
+            video.work_buffer,
+            video.background_buffer,
+            video.front_buffer,
+            video.back_buffer,
+        ] {
+            writer.write_u8(buffer as u8)?;
+        }
+        writer.write_u8(video.palette_id.unwrap_or(NO_PALETTE))?;
+        for page in &video.pages {
+            writer.write_u32::<BigEndian>(page.len() as u32)?;
+            writer.write_all(page)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads the snapshot at `path`, rejecting pages that are not
+    /// `page_size` bytes before anything is allocated for them.
+    pub fn load(path: &Path, page_size: usize) -> Result<Self, SnapshotError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.read_u8()?;
+        if !(FIRST_VERSION..=VERSION).contains(&version) {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let part = GamePart::try_from(reader.read_u16::<BigEndian>()?)?;
+        let asset_count = reader.read_u16::<BigEndian>()?;
+        let assets = (0..asset_count)
+            .map(|_| reader.read_u16::<BigEndian>().map(usize::from))
+            .collect::<Result<_, _>>()?;
+
+        let mut vm = VmState::default();
+        reader.read_i16_into::<BigEndian>(&mut vm.variables)?;
+        for channel in vm.channels.iter_mut() {
+            *channel = Self::read_channel(&mut reader)?;
+        }
+        if version > FIRST_VERSION {
+            let channel_id = reader.read_u8()?;
+            if channel_id as usize >= vm.channels.len() {
+                return Err(SnapshotError::InvalidChannel(channel_id));
+            }
+            vm.running_channel_id = channel_id.into();
+        }
+
+        let mut video = VideoState::default();
+        for buffer in [
+            &mut video.work_buffer,
+            &mut video.background_buffer,
+            &mut video.front_buffer,
+            &mut video.back_buffer,
+        ] {
+            *buffer = match reader.read_u8()? {
+                page @ 0..=3 => page.into(),
+                page => return Err(SnapshotError::InvalidPage(page)),
+            };
+        }
+        if version >= PALETTE_VERSION {
+            video.palette_id = match reader.read_u8()? {
+                NO_PALETTE => None,
+                palette_id @ 0..32 => Some(palette_id),
+                palette_id => return Err(SnapshotError::InvalidPalette(palette_id)),
+            };
+        }
+        for page in video.pages.iter_mut() {
+            let len = reader.read_u32::<BigEndian>()? as usize;
+            if len != page_size {
+                return Err(SnapshotError::PageSizeMismatch {
+                    expected: page_size,
+                    actual: len,
+                });
+            }
+            page.resize(len, 0);
+            reader.read_exact(page)?;
+        }
+
+        Ok(Self {
+            part,
+            assets,
+            vm,
+            video,
+        })
+    }
+
+    fn write_channel(writer: &mut impl Write, channel: &Channel) -> Result<(), SnapshotError> {
+        // Snapshots are taken between frames, but a channel caught running
+        // would have to start its slice over anyway.
+        let state = match channel.state {
+            State::Ready | State::Running => 0,
+            State::Paused => 1,
+            State::Dead => 2,
+        };
+        writer.write_u8(state)?;
+        Self::write_pc(writer, channel.pc)?;
+        writer.write_u8(channel.next_pc.is_some() as u8)?;
+        Self::write_pc(writer, channel.next_pc.unwrap_or(ProcessCounter::Invalid))?;
+        Ok(())
+    }
+
+    fn read_channel(reader: &mut impl Read) -> Result<Channel, SnapshotError> {
+        let state = match reader.read_u8()? {
+            0 => State::Ready,
+            1 => State::Paused,
+            2 => State::Dead,
+            state => return Err(SnapshotError::InvalidChannelState(state)),
+        };
+        let pc = Self::read_pc(reader)?;
+        let has_next_pc = reader.read_u8()? != 0;
+        let next_pc = Self::read_pc(reader)?;
+        Ok(Channel {
+            state,
+            pc,
+            next_pc: has_next_pc.then_some(next_pc),
+        })
+    }
+
+    fn write_pc(writer: &mut impl Write, pc: ProcessCounter) -> io::Result<()> {
+        let raw = pc.offset().map_or(INVALID_PC, |offset| offset as u32);
+        writer.write_u32::<BigEndian>(raw)
+    }
+
+    fn read_pc(reader: &mut impl Read) -> io::Result<ProcessCounter> {
+        Ok(match reader.read_u32::<BigEndian>()? {
+            INVALID_PC => ProcessCounter::Invalid,
+            offset => ProcessCounter::Valid(offset as usize),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::*;
+
+    const PAGE_SIZE: usize = 64;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("arw-{name}-{}.arws", std::process::id()))
+    }
+
+    fn snapshot(page_size: usize) -> Snapshot {
+        let mut video = VideoState::default();
+        for (index, page) in video.pages.iter_mut().enumerate() {
+            *page = vec![index as u8; page_size];
+        }
+        Snapshot {
+            part: GamePart::Two,
+            assets: vec![0x12],
+            vm: VmState::default(),
+            video,
+        }
+    }
+
+    #[test]
+    fn round_trips_running_channel_as_ready() {
+        let path = temp_path("round-trip");
+        let mut saved = snapshot(PAGE_SIZE);
+        saved.vm.variables[0x10] = -5;
+        saved.vm.channels[7] = Channel {
+            state: State::Running,
+            pc: ProcessCounter::Valid(0x123),
+            next_pc: Some(ProcessCounter::Invalid),
+        };
+        saved.vm.running_channel_id = 7;
+        saved.video.front_buffer = 2;
+        saved.video.palette_id = Some(0x1F);
+        saved.save(&path).unwrap();
+        let loaded = Snapshot::load(&path, PAGE_SIZE);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.part, GamePart::Two);
+        assert_eq!(loaded.assets, [0x12]);
+        assert_eq!(loaded.vm.variables[0x10], -5);
+        let channel = loaded.vm.channels[7];
+        assert!(channel.state == State::Ready);
+        assert_eq!(channel.pc.offset(), Some(0x123));
+        assert!(matches!(channel.next_pc, Some(ProcessCounter::Invalid)));
+        assert_eq!(loaded.vm.running_channel_id, 7);
+        assert_eq!(loaded.video.front_buffer, 2);
+        assert_eq!(loaded.video.palette_id, Some(0x1F));
+        assert_eq!(loaded.video.pages[3], vec![3; PAGE_SIZE]);
+    }
+
+    #[test]
+    fn rejects_page_size_before_reading_pages() {
+        let path = temp_path("page-size");
+        snapshot(PAGE_SIZE * 2).save(&path).unwrap();
+        let result = Snapshot::load(&path, PAGE_SIZE);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(SnapshotError::PageSizeMismatch {
+                expected: PAGE_SIZE,
+                actual: 128,
+            })
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+/// Playback rates in Hz selected by the `freq` operand of `op_play_sound`,
+/// as found in the original interpreter. They follow the Amiga period table,
+/// one semitone apart.
+pub const FREQUENCY_TABLE: [u16; 40] = [
+    0x0CFF, 0x0DC3, 0x0E91, 0x0F6F, 0x1056, 0x114E, 0x1259, 0x136C, 0x149F, 0x15D9, 0x1726, 0x1888,
+    0x19FD, 0x1B86, 0x1D21, 0x1EDE, 0x20AB, 0x229C, 0x24B3, 0x26D7, 0x293F, 0x2BB2, 0x2E4C, 0x3110,
+    0x33FB, 0x370D, 0x3A43, 0x3DDF, 0x4157, 0x4538, 0x4998, 0x4DAE, 0x5240, 0x5764, 0x5C9A, 0x61C8,
+    0x6793, 0x6E19, 0x7485, 0x7BBD,
+];
+
+/// Playback rate for a period index. Indices past the table are clamped to
+/// the highest rate, like the original does.
+pub fn frequency_for(index: u8) -> u32 {
+    let index = (index as usize).min(FREQUENCY_TABLE.len() - 1);
+    FREQUENCY_TABLE[index].into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequency_for_reads_the_table() {
+        assert_eq!(frequency_for(0), 3327);
+        assert_eq!(frequency_for(20), 10559);
+        assert_eq!(frequency_for(39), 31677);
+    }
+
+    #[test]
+    fn frequency_for_clamps_past_the_table() {
+        for index in [40, 41, 0xFF] {
+            assert_eq!(frequency_for(index), 31677);
+        }
+    }
+}
@@ -1,30 +1,214 @@
-use std::{process, time::Duration};
+use std::time::Duration;
 
 use winit::{
-    event::{Event, WindowEvent},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, Event, KeyEvent, MouseButton, WindowEvent},
     event_loop::EventLoop,
+    keyboard::{KeyCode, PhysicalKey},
     platform::pump_events::EventLoopExtPumpEvents,
 };
 
+#[cfg(feature = "gamepad")]
+use crate::gamepad::GamepadInput;
+use crate::{
+    input::{InputSource, InputState},
+    renderer::Aspect,
+};
+
+/// Horizontal motion, in pixels, needed before the mouse reads as a
+/// direction.
+const MOUSE_DEADZONE: f64 = 2.0;
+/// Cap on the pending motion, so a fast swipe holds the direction for a few
+/// frames at most instead of until the whole distance is consumed.
+const MOUSE_MAX_MOTION: f64 = 32.0;
+
 pub struct SysEventHandler {
     event_loop: EventLoop<()>,
+    input: InputState,
+    input_source: InputSource,
+    mouse: InputState,
+    mouse_motion: f64,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<GamepadInput>,
+    gamepad_input: InputState,
+    action_tapped: bool,
+    key_presses: Vec<KeyCode>,
+    quit_requested: bool,
+    cursor: Option<PhysicalPosition<f64>>,
+    window_size: PhysicalSize<u32>,
 }
 
 impl SysEventHandler {
-    pub fn new(event_loop: EventLoop<()>) -> Self {
-        Self { event_loop }
+    /// Without the `gamepad` feature, `InputSource::Gamepad` reads the
+    /// keyboard alone.
+    pub fn new(
+        event_loop: EventLoop<()>,
+        window_size: PhysicalSize<u32>,
+        input_source: InputSource,
+    ) -> Self {
+        #[cfg(not(feature = "gamepad"))]
+        if input_source == InputSource::Gamepad {
+            log::warn!("Built without the gamepad feature, reading the keyboard only");
+        }
+        Self {
+            event_loop,
+            input: InputState::default(),
+            input_source,
+            mouse: InputState::default(),
+            mouse_motion: 0.0,
+            #[cfg(feature = "gamepad")]
+            gamepad: (input_source == InputSource::Gamepad)
+                .then(GamepadInput::new)
+                .flatten(),
+            gamepad_input: InputState::default(),
+            action_tapped: false,
+            key_presses: Vec::new(),
+            quit_requested: false,
+            cursor: None,
+            window_size,
+        }
+    }
+
+    /// Input for the next frame. Directions are the state held right now,
+    /// while action also reads as pressed once after a tap that was
+    /// released before the frame sampled it.
+    pub fn take_input(&mut self) -> InputState {
+        let mut input = match self.input_source {
+            InputSource::Keyboard => self.input,
+            InputSource::Mouse => self.mouse,
+            InputSource::Both => self.input.merge(self.mouse),
+            InputSource::Gamepad => self.input.merge(self.gamepad_input),
+        };
+        input.action |= std::mem::take(&mut self.action_tapped);
+        input
+    }
+
+    pub fn take_key_presses(&mut self) -> Vec<KeyCode> {
+        std::mem::take(&mut self.key_presses)
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    /// Source pixel under the mouse cursor, if it is over the picture.
+    pub fn cursor_on_screen(&self, aspect: Aspect) -> Option<(usize, usize)> {
+        let cursor = self.cursor?;
+        aspect.screen_position(
+            self.window_size.width as usize,
+            self.window_size.height as usize,
+            cursor.x,
+            cursor.y,
+        )
     }
 
     pub fn pump_events(&mut self) {
+        let input = &mut self.input;
+        let key_presses = &mut self.key_presses;
+        let quit_requested = &mut self.quit_requested;
+        let cursor = &mut self.cursor;
+        let window_size = &mut self.window_size;
+        let mouse = &mut self.mouse;
+        let mouse_motion = &mut self.mouse_motion;
+        let action_tapped = &mut self.action_tapped;
+        let reads_keyboard = self.input_source != InputSource::Mouse;
+        let reads_mouse = matches!(self.input_source, InputSource::Mouse | InputSource::Both);
         self.event_loop
-            .pump_events(Some(Duration::ZERO), |event, _| {
-                if let Event::WindowEvent {
+            .pump_events(Some(Duration::ZERO), |event, _| match event {
+                Event::WindowEvent {
                     event: WindowEvent::CloseRequested,
                     ..
-                } = event
-                {
-                    process::exit(0);
+                } => *quit_requested = true,
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => *window_size = size,
+                // Key releases are not delivered to an unfocused window, so
+                // drop whatever was held rather than keep Lester walking.
+                Event::WindowEvent {
+                    event: WindowEvent::Focused(false),
+                    ..
+                } => {
+                    *input = InputState::default();
+                    mouse.action = false;
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => {
+                    if let Some(previous) = cursor.replace(position) {
+                        *mouse_motion = (*mouse_motion + position.x - previous.x)
+                            .clamp(-MOUSE_MAX_MOTION, MOUSE_MAX_MOTION);
+                    }
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::MouseInput {
+                            state,
+                            button: MouseButton::Left,
+                            ..
+                        },
+                    ..
+                } => {
+                    mouse.action = state == ElementState::Pressed;
+                    *action_tapped |= reads_mouse && mouse.action;
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorLeft { .. },
+                    ..
+                } => *cursor = None,
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            event:
+                                KeyEvent {
+                                    physical_key: PhysicalKey::Code(key_code),
+                                    state,
+                                    repeat,
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    let pressed = state == ElementState::Pressed;
+                    if pressed && !repeat {
+                        key_presses.push(key_code);
+                        *action_tapped |= reads_keyboard && Self::is_action_key(key_code);
+                    }
+                    Self::update_input(input, key_code, pressed);
+                }
+                _ => {}
             });
+        self.update_mouse_direction();
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = self.gamepad.as_mut() {
+            let gamepad_input = gamepad.poll();
+            self.action_tapped |= gamepad_input.action && !self.gamepad_input.action;
+            self.gamepad_input = gamepad_input;
+        }
+    }
+
+    /// Turns the pending horizontal motion into a direction, then halves it
+    /// so the direction is released once the mouse stops.
+    fn update_mouse_direction(&mut self) {
+        self.mouse.left = self.mouse_motion <= -MOUSE_DEADZONE;
+        self.mouse.right = self.mouse_motion >= MOUSE_DEADZONE;
+        self.mouse_motion /= 2.0;
+    }
+
+    fn update_input(input: &mut InputState, key_code: KeyCode, pressed: bool) {
+        match key_code {
+            KeyCode::ArrowLeft => input.left = pressed,
+            KeyCode::ArrowRight => input.right = pressed,
+            KeyCode::ArrowUp => input.up = pressed,
+            KeyCode::ArrowDown => input.down = pressed,
+            key_code if Self::is_action_key(key_code) => input.action = pressed,
+            _ => {}
+        }
+    }
+
+    fn is_action_key(key_code: KeyCode) -> bool {
+        matches!(key_code, KeyCode::Space | KeyCode::Enter)
     }
 }
@@ -0,0 +1,130 @@
+use std::io::{self, Read, Write};
+
+use crate::color_vision::ColorVision;
+use crate::renderer::{
+    NUM_COLORS, Renderer, RendererError, SCREEN_H, SCREEN_W, act_to_palette, read_palette,
+};
+
+const TERM_COLS: usize = 80;
+const TERM_ROWS: usize = 25;
+const CELL_W: usize = SCREEN_W / TERM_COLS;
+const CELL_H: usize = SCREEN_H / TERM_ROWS;
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+pub struct TerminalRenderer {
+    palette: [u32; NUM_COLORS],
+    ansi_palette: [u8; NUM_COLORS],
+    frame: String,
+    color_vision: ColorVision,
+}
+
+impl Default for TerminalRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalRenderer {
+    pub fn new() -> Self {
+        Self {
+            palette: Default::default(),
+            ansi_palette: Default::default(),
+            frame: String::new(),
+            color_vision: ColorVision::default(),
+        }
+    }
+
+    fn store_palette(&mut self, palette: [u32; NUM_COLORS]) {
+        self.palette = palette;
+        self.ansi_palette = palette.map(Self::nearest_ansi_color);
+    }
+
+    fn pixel_at(src: &[u8], x: usize, y: usize) -> usize {
+        let two_pixels_byte = src[y * SCREEN_W / 2 + x / 2];
+        if x & 1 == 0 {
+            (two_pixels_byte >> 4) as usize
+        } else {
+            (two_pixels_byte & 0x0F) as usize
+        }
+    }
+
+    fn nearest_ansi_color(rgb: u32) -> u8 {
+        let channels = [(rgb >> 16) & 0xFF, (rgb >> 8) & 0xFF, rgb & 0xFF];
+        let cube_level = |c: u32| -> u32 {
+            match c {
+                0..=47 => 0,
+                48..=114 => 1,
+                _ => (c - 35) / 40,
+            }
+        };
+        let cube_value = |level: u32| -> u32 { if level == 0 { 0 } else { 55 + level * 40 } };
+        let distance = |a: [u32; 3], b: [u32; 3]| -> u32 {
+            a.iter()
+                .zip(b.iter())
+                .map(|(&x, &y)| x.abs_diff(y).pow(2))
+                .sum()
+        };
+
+        let levels = channels.map(cube_level);
+        let cube_rgb = levels.map(cube_value);
+        let cube_idx = 16 + 36 * levels[0] + 6 * levels[1] + levels[2];
+
+        let average = channels.iter().sum::<u32>() / 3;
+        let gray_level = if average > 238 {
+            23
+        } else {
+            average.saturating_sub(3) / 10
+        };
+        let gray_value = 8 + gray_level * 10;
+        let gray_idx = 232 + gray_level;
+
+        if distance(channels, [gray_value; 3]) < distance(channels, cube_rgb) {
+            gray_idx as u8
+        } else {
+            cube_idx as u8
+        }
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    fn set_palette(&mut self, reader: &mut dyn Read) -> Result<(), RendererError> {
+        self.store_palette(self.color_vision.apply(read_palette(reader)?));
+        Ok(())
+    }
+
+    fn update_display(&mut self, src: &[u8]) -> Result<(), RendererError> {
+        self.frame.clear();
+        self.frame.push_str("\x1b[2J\x1b[H");
+        for row in 0..TERM_ROWS {
+            let top_y = row * CELL_H;
+            let bottom_y = top_y + CELL_H / 2;
+            for col in 0..TERM_COLS {
+                let x = col * CELL_W;
+                let top = self.ansi_palette[Self::pixel_at(src, x, top_y)];
+                let bottom = self.ansi_palette[Self::pixel_at(src, x, bottom_y)];
+                self.frame.push_str(&format!(
+                    "\x1b[38;5;{top}m\x1b[48;5;{bottom}m{UPPER_HALF_BLOCK}"
+                ));
+            }
+            self.frame.push_str("\x1b[0m\r\n");
+        }
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(self.frame.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn palette(&self) -> [u32; NUM_COLORS] {
+        self.palette
+    }
+
+    fn import_palette(&mut self, data: &[u8]) -> Result<(), RendererError> {
+        self.store_palette(act_to_palette(data)?);
+        Ok(())
+    }
+
+    fn set_color_vision(&mut self, color_vision: ColorVision) {
+        self.color_vision = color_vision;
+    }
+}
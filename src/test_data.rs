@@ -0,0 +1,72 @@
+use std::{cell::RefCell, io, rc::Rc};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::{
+    bank::{BankError, BankSource, MemoryBankSource},
+    mem_entry::END_OF_LIST,
+    resource::NUM_MEM_ENTRIES,
+};
+
+/// A full-size memlist where entry `i` is stored unpacked at the start of
+/// bank `i`. Only `entries` get a bank, reading any other entry fails.
+pub fn bank_source(entries: &[(usize, Vec<u8>)]) -> MemoryBankSource {
+    short_bank_source(entries, NUM_MEM_ENTRIES.into())
+}
+
+/// Like `bank_source`, with only the first `num_entries` memlist entries.
+pub fn short_bank_source(entries: &[(usize, Vec<u8>)], num_entries: usize) -> MemoryBankSource {
+    let mut mem_list = Vec::new();
+    let mut source = MemoryBankSource::default();
+    for index in 0..num_entries {
+        let data = entries
+            .iter()
+            .find(|(entry, _)| *entry == index)
+            .map(|(_, data)| data.clone());
+        let size = data.as_ref().map_or(0, Vec::len) as u32;
+        mem_list.extend([0, 0, 0, 0, 0, 0, 0, index as u8]);
+        mem_list.write_u32::<BigEndian>(0).unwrap();
+        mem_list.write_u32::<BigEndian>(size).unwrap();
+        mem_list.write_u32::<BigEndian>(size).unwrap();
+        if let Some(data) = data {
+            source.insert(index as u8, data);
+        }
+    }
+    mem_list.push(END_OF_LIST);
+    mem_list.resize(mem_list.len() + 19, 0);
+    source.set_mem_list(mem_list);
+    source
+}
+
+/// Wraps a source built by `bank_source` and logs the memlist entries read
+/// from it.
+pub struct TracingBankSource {
+    inner: MemoryBankSource,
+    reads: Rc<RefCell<Vec<usize>>>,
+}
+
+impl TracingBankSource {
+    pub fn new(inner: MemoryBankSource) -> (Self, Rc<RefCell<Vec<usize>>>) {
+        let reads = Rc::default();
+        let source = Self {
+            inner,
+            reads: Rc::clone(&reads),
+        };
+        (source, reads)
+    }
+}
+
+impl BankSource for TracingBankSource {
+    fn read_mem_list(&self) -> io::Result<Vec<u8>> {
+        self.inner.read_mem_list()
+    }
+
+    fn bank_len(&self, bank_id: u8) -> Result<u64, BankError> {
+        self.inner.bank_len(bank_id)
+    }
+
+    fn read_at(&mut self, bank_id: u8, offset: u64, buf: &mut [u8]) -> Result<(), BankError> {
+        self.reads.borrow_mut().push(bank_id.into());
+        self.inner.read_at(bank_id, offset, buf)
+    }
+}
@@ -1,6 +1,37 @@
 use std::{collections::HashMap, sync::LazyLock};
 
-pub static STRING_TABLE: LazyLock<HashMap<u16, &[u8]>> = LazyLock::new(|| {
+use clap::ValueEnum;
+use log::warn;
+use serde::Deserialize;
+
+/// Drawn in place of string ids missing from both tables.
+const UNKNOWN_STRING: &[u8] = b"???";
+
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Fr,
+}
+
+/// The French table only holds the translated strings, so the others fall
+/// back to the English one.
+pub fn lookup_string(language: Language, string_id: u16) -> &'static [u8] {
+    let translated = match language {
+        Language::En => None,
+        Language::Fr => STRING_TABLE_FR.get(&string_id),
+    };
+    translated
+        .or_else(|| STRING_TABLE_EN.get(&string_id))
+        .copied()
+        .unwrap_or_else(|| {
+            warn!("Unknown string id {string_id:#05X}");
+            UNKNOWN_STRING
+        })
+}
+
+pub static STRING_TABLE_EN: LazyLock<HashMap<u16, &[u8]>> = LazyLock::new(|| {
     let mut strings: HashMap<u16, &[u8]> = HashMap::new();
     strings.insert(0x001, b"P E A N U T  3000");
     strings.insert(
@@ -169,6 +200,56 @@ pub static STRING_TABLE: LazyLock<HashMap<u16, &[u8]>> = LazyLock::new(|| {
     strings
 });
 
+pub static STRING_TABLE_FR: LazyLock<HashMap<u16, &[u8]>> = LazyLock::new(|| {
+    let mut strings: HashMap<u16, &[u8]> = HashMap::new();
+    strings.insert(0x001, b"P E A N U T  3000");
+    strings.insert(
+        0x002,
+        b"Copyright  } 1990 Peanut Computer, Inc.\x0AAll rights reserved.\x0A\x0ACDOS Version 5.01",
+    );
+    strings.insert(0x003, b"2");
+    strings.insert(0x004, b"3");
+    strings.insert(0x005, b".");
+    strings.insert(0x006, b"A");
+    strings.insert(0x007, b"@");
+    strings.insert(0x008, b"PEANUT 3000");
+    strings.insert(0x00A, b"R");
+    strings.insert(0x00B, b"U");
+    strings.insert(0x00C, b"N");
+    strings.insert(0x00D, b"P");
+    strings.insert(0x00E, b"R");
+    strings.insert(0x00F, b"O");
+    strings.insert(0x010, b"J");
+    strings.insert(0x011, b"E");
+    strings.insert(0x012, b"C");
+    strings.insert(0x013, b"T");
+    strings.insert(0x014, b"Shield 9A.5f Ok");
+    strings.insert(0x015, b"Flux % 5.0177 Ok");
+    strings.insert(0x016, b"CDI Vector ok");
+    strings.insert(0x017, b" %%%ddd ok");
+    strings.insert(0x018, b"Race-Track ok");
+    strings.insert(0x18E, b"PLACEZ LA MANETTE EN BAS A DROITE");
+    strings.insert(0x258, b"      Conception ..... Eric Chahi");
+    strings.insert(0x259, b"    Programmation ..... Eric Chahi");
+    strings.insert(0x25A, b"     Graphismes ....... Eric Chahi");
+    strings.insert(0x25B, b"Musique de ...... Jean-francois Freitas");
+    strings.insert(0x25C, b"              Bruitages");
+    strings.insert(
+        0x25D,
+        b"        Jean-Francois Freitas\x0A             Eric Chahi",
+    );
+    strings.insert(0x263, b"               Merci a");
+    strings.insert(0x264, b"           Jesus Martinez\x0A\x0A          Daniel Morais\x0A\x0A        Frederic Savoir\x0A\x0A      Cecile Chahi\x0A\x0A    Philippe Delamarre\x0A\x0A  Philippe Ulrich\x0A\x0ASebastien Berthet\x0A\x0APierre Gousseau");
+    strings.insert(0x265, b"Now Go Back To Another Earth");
+    strings.insert(0x190, b"Bonsoir professeur.");
+    strings.insert(0x191, b"Je vois que Monsieur a pris\x0Asa Ferrari.");
+    strings.insert(0x192, b"IDENTIFICATION");
+    strings.insert(0x193, b"Monsieur est en parfaite sante.");
+    strings.insert(0x194, b"O\x0A");
+    strings.insert(0x193, b"AU BOULOT !!!\x0A");
+    strings
+});
+
 pub static FONT: [u8; 768] = [
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x10, 0x00,
     0x28, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x7E, 0x24, 0x24, 0x7E, 0x24, 0x00,
@@ -219,3 +300,17 @@ pub static FONT: [u8; 768] = [
     0xFE, 0xFE, 0xFE, 0xFE, 0xFE, 0xFE, 0xFE, 0x00, 0x38, 0x44, 0xBA, 0xA2, 0xBA, 0x44, 0x38, 0x00,
     0x38, 0x44, 0x82, 0x82, 0x44, 0x28, 0xEE, 0x00, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn french_falls_back_to_english() {
+        assert!(!STRING_TABLE_FR.contains_key(&0x019));
+        assert_eq!(lookup_string(Language::Fr, 0x019), STRING_TABLE_EN[&0x019]);
+        assert_eq!(lookup_string(Language::Fr, 0x18E), STRING_TABLE_FR[&0x18E]);
+        assert_eq!(lookup_string(Language::En, 0x18E), STRING_TABLE_EN[&0x18E]);
+        assert_eq!(lookup_string(Language::Fr, 0xFFF), UNKNOWN_STRING);
+    }
+}
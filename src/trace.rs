@@ -0,0 +1,84 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::{parts::GamePart, vm::FrameCounters};
+
+/// Frames between two flushes, about a second of play, so a crash or a
+/// killed process loses little of the trace.
+const FLUSH_INTERVAL_FRAMES: u64 = 50;
+const HEADER: &str = "frame,part,channels_run,opcodes,draw_calls,polygons,error";
+
+#[derive(Error, Debug)]
+pub enum TraceError {
+    #[error("IO error while writing the trace")]
+    Io(#[source] io::Error),
+}
+
+impl From<io::Error> for TraceError {
+    fn from(value: io::Error) -> Self {
+        TraceError::Io(value)
+    }
+}
+
+/// Appends one CSV line per frame with the part and the work the VM did,
+/// plus the error chain if the frame failed.
+pub struct FrameTracer {
+    writer: BufWriter<File>,
+    frame: u64,
+}
+
+impl FrameTracer {
+    pub fn create(path: &Path) -> Result<Self, TraceError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{HEADER}")?;
+        writer.flush()?;
+        Ok(Self { writer, frame: 0 })
+    }
+
+    pub fn record(
+        &mut self,
+        part: GamePart,
+        counters: FrameCounters,
+        error: Option<&dyn Error>,
+    ) -> Result<(), TraceError> {
+        let error = error.map(Self::quoted_chain).unwrap_or_default();
+        writeln!(
+            self.writer,
+            "{},{:#06X},{},{},{},{},{error}",
+            self.frame,
+            part as u16,
+            counters.channels_run,
+            counters.opcodes,
+            counters.draw_calls,
+            counters.polygons,
+        )?;
+        self.frame += 1;
+        if error.is_empty() && !self.frame.is_multiple_of(FLUSH_INTERVAL_FRAMES) {
+            return Ok(());
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn quoted_chain(error: &dyn Error) -> String {
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {cause}"));
+            source = cause.source();
+        }
+        format!("\"{}\"", message.replace('"', "\"\""))
+    }
+}
+
+impl Drop for FrameTracer {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
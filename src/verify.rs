@@ -0,0 +1,82 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    coverage::OpcodeCoverage,
+    engine::{Engine, EngineConfig, ErrorPolicy},
+    headless::HeadlessRenderer,
+    input::InputState,
+    parts::GamePart,
+};
+
+const VERIFY_SEED: i16 = 0;
+pub const DEFAULT_VERIFY_FRAMES: usize = 1000;
+
+pub struct VerifyReport {
+    part: GamePart,
+    frames: usize,
+    completed: usize,
+    error: Option<String>,
+    coverage: Option<OpcodeCoverage>,
+}
+
+impl VerifyReport {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(coverage) = &self.coverage {
+            writeln!(f, "{coverage}")?;
+        }
+        match &self.error {
+            None => write!(
+                f,
+                "PASS {}: {} frames without errors",
+                self.part, self.completed
+            ),
+            Some(error) => write!(
+                f,
+                "FAIL {}: error after {}/{} frames: {error}",
+                self.part, self.completed, self.frames
+            ),
+        }
+    }
+}
+
+/// Runs `part` headless for `frames` frames, aborting on the first error.
+pub fn run(config: &EngineConfig, part: GamePart, frames: usize) -> VerifyReport {
+    let config = EngineConfig {
+        part: Some(part),
+        error_policy: ErrorPolicy::Abort,
+        ..config.clone()
+    };
+    let mut report = VerifyReport {
+        part,
+        frames,
+        completed: 0,
+        error: None,
+        coverage: None,
+    };
+
+    let mut engine = match Engine::new(&config, Box::new(HeadlessRenderer::default()), VERIFY_SEED)
+    {
+        Ok(engine) => engine,
+        Err(e) => {
+            report.error = Some(format!("{e:?}"));
+            return report;
+        }
+    };
+    engine.set_uncapped(true);
+
+    for _ in 0..frames {
+        if let Err(e) = engine.tick(InputState::default()) {
+            report.error = Some(format!("{e:?}"));
+            break;
+        }
+        report.completed += 1;
+    }
+    report.coverage = engine.opcode_coverage().cloned();
+    report
+}
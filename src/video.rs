@@ -1,27 +1,47 @@
 use std::{
-    cmp::{max, min},
-    io::{self, Cursor, Seek},
+    borrow::Cow,
+    cmp::{Ordering, max, min},
+    io::{self, Read, Seek, SeekFrom},
+    thread,
+    time::Duration,
 };
 
 use byteorder::{BigEndian, ReadBytesExt};
+use clap::ValueEnum;
+use serde::Deserialize;
 use thiserror::Error;
 
 use crate::{
-    renderer::{Renderer, RendererError},
+    color_vision::ColorVision,
+    compat::{Quirk, Quirks},
+    renderer::{
+        ACT_PALETTE_SIZE, EDGE_COVERAGE, EDGE_RIGHT, NUM_COLORS, Renderer, RendererError, SCREEN_H,
+        SCREEN_W, downscale,
+    },
     shapes::{Point, Polygon},
-    text::{FONT, STRING_TABLE},
+    text::{FONT, Language, lookup_string},
 };
 
-const HEIGHT: usize = 200;
-const WIDTH: usize = 320;
-const VID_PAGE_SIZE: usize = HEIGHT * WIDTH / 2;
+#[cfg(feature = "profile")]
+use crate::render_profile::RenderProfile;
+#[cfg(feature = "profile")]
+use std::time::Instant;
+
+const HEIGHT: usize = SCREEN_H;
+const WIDTH: usize = SCREEN_W;
+const OVERLAY_COLOR: u8 = 0x0F;
+const BOUNDING_BOX_COLOR: u8 = 0x0F;
+const DRAW_ORDER_DELAY: Duration = Duration::from_millis(15);
+/// Consecutive single-color frames, about ten seconds of play, after which
+/// the screen is reported as stuck.
+pub const UNIFORM_FRAMES_WARNING: usize = 500;
 
 #[derive(Error, Debug)]
 pub enum VideoError {
     #[error("Error in the underlying stream")]
-    Io(io::Error),
+    Io(#[source] io::Error),
     #[error("Renderer error")]
-    RendererError(RendererError),
+    RendererError(#[source] RendererError),
     #[error("Invalid palette number {0}")]
     InvalidPalette(u8),
     #[error("Unexpected command")]
@@ -43,6 +63,9 @@ impl From<RendererError> for VideoError {
 #[derive(PartialEq, Debug)]
 pub enum PageId {
     Numbered(u8),
+    /// A numbered page with the 0x80 flag, which `copy_page` scrolls
+    /// vertically while copying.
+    NumberedScrolled(u8),
     Front,
     Back,
 }
@@ -52,136 +75,406 @@ impl PageId {
         match raw_page_id {
             0xFE => PageId::Front,
             0xFF => PageId::Back,
-            n if n <= 3 => PageId::Numbered(n),
+            n if n & 0x80 != 0 => PageId::NumberedScrolled(n & 3),
+            n if n & 0xBF <= 3 => PageId::Numbered(n & 0xBF),
             _ => PageId::Numbered(0),
         }
     }
 }
 
+/// How the 0x10 "blend" color used for shadows and water is drawn.
+/// `Authentic` sets the top bit of the underlying color index like the
+/// original engine, `Alpha` replaces it with the palette entry closest to the
+/// halfway point between the underlying color and its shaded counterpart.
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    #[default]
+    Authentic,
+    Alpha,
+}
+
+/// Resolution the pages are drawn at, as a multiple of the original
+/// 320x200. Script coordinates, polygon zoom, text and backgrounds are all
+/// scaled up, so polygon edges are rasterized with finer steps.
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InternalScale {
+    #[default]
+    #[value(name = "1x")]
+    #[serde(rename = "1x")]
+    X1,
+    #[value(name = "2x")]
+    #[serde(rename = "2x")]
+    X2,
+    #[value(name = "4x")]
+    #[serde(rename = "4x")]
+    X4,
+}
+
+impl InternalScale {
+    pub fn factor(self) -> usize {
+        match self {
+            InternalScale::X1 => 1,
+            InternalScale::X2 => 2,
+            InternalScale::X4 => 4,
+        }
+    }
+}
+
 pub enum PaletteRequest {
     Change(u8),
     Keep,
 }
 
+/// Page contents and buffer selection, enough to resume drawing and to
+/// present the same frame again.
+#[derive(Clone, Default)]
+pub struct VideoState {
+    pub(crate) pages: [Vec<u8>; 4],
+    pub(crate) work_buffer: usize,
+    pub(crate) background_buffer: usize,
+    pub(crate) front_buffer: usize,
+    pub(crate) back_buffer: usize,
+    /// Last palette applied, which the pages are meant to be shown with.
+    pub(crate) palette_id: Option<u8>,
+}
+
 pub struct Video {
     hline_y: i16,
-    pages: [[u8; VID_PAGE_SIZE]; 4],
+    scale: usize,
+    width: i16,
+    height: i16,
+    pages: [Vec<u8>; 4],
+    /// Polygon span-edge coverage of each page, one byte per pixel, for the
+    /// renderer's anti-aliasing.
+    edges: [Vec<u8>; 4],
     work_buffer: usize,
+    background_buffer: usize,
     front_buffer: usize,
     back_buffer: usize,
     palette_req: PaletteRequest,
-    renderer: Renderer,
+    palette_id: Option<u8>,
+    renderer: Box<dyn Renderer>,
+    overlay: Vec<String>,
+    debug_palette: bool,
+    palette_locked: bool,
+    blend_mode: BlendMode,
+    blend_table: [u8; NUM_COLORS],
+    frames_presented: usize,
+    uniform_since: Option<usize>,
+    polygons_drawn: usize,
+    vertices_read: usize,
+    vertices_filled: usize,
+    draw_order: bool,
+    bounding_boxes: bool,
+    present: bool,
+    quirks: Quirks,
+    language: Language,
+    #[cfg(feature = "profile")]
+    profile: RenderProfile,
 }
 
 impl Video {
-    pub fn new(renderer: Renderer) -> Self {
+    pub fn new(renderer: Box<dyn Renderer>) -> Self {
         Video {
             hline_y: 0,
-            pages: [[0; VID_PAGE_SIZE]; 4],
+            scale: 1,
+            width: WIDTH as i16,
+            height: HEIGHT as i16,
+            pages: std::array::from_fn(|_| vec![0; WIDTH * HEIGHT / 2]),
+            edges: std::array::from_fn(|_| vec![0; WIDTH * HEIGHT]),
             work_buffer: 2,
+            background_buffer: 0,
             front_buffer: 2,
             back_buffer: 1,
             palette_req: PaletteRequest::Keep,
+            palette_id: None,
             renderer,
+            overlay: Vec::new(),
+            debug_palette: false,
+            palette_locked: false,
+            blend_mode: BlendMode::default(),
+            blend_table: Self::authentic_blend_table(),
+            frames_presented: 0,
+            uniform_since: None,
+            polygons_drawn: 0,
+            vertices_read: 0,
+            vertices_filled: 0,
+            draw_order: false,
+            bounding_boxes: false,
+            present: true,
+            quirks: Quirks::default(),
+            language: Language::default(),
+            #[cfg(feature = "profile")]
+            profile: RenderProfile::default(),
         }
     }
 
+    /// Resizes the pages to the given resolution, clearing their contents.
+    pub fn set_internal_scale(&mut self, internal_scale: InternalScale) {
+        self.scale = internal_scale.factor();
+        self.width = (WIDTH * self.scale) as i16;
+        self.height = (HEIGHT * self.scale) as i16;
+        let page_size = self.stride() * self.height as usize;
+        self.pages = std::array::from_fn(|_| vec![0; page_size]);
+        self.edges = std::array::from_fn(|_| vec![0; page_size * 2]);
+    }
+
+    fn stride(&self) -> usize {
+        self.width as usize / 2
+    }
+
+    /// Byte holding the pixel at `x` on the current scanline.
+    fn line_offset(&self, x: i16) -> usize {
+        self.hline_y as usize * self.stride() + x as usize / 2
+    }
+
+    pub fn frames_presented(&self) -> usize {
+        self.frames_presented
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+        self.update_blend_table();
+    }
+
+    fn authentic_blend_table() -> [u8; NUM_COLORS] {
+        std::array::from_fn(|color| color as u8 | 0x08)
+    }
+
+    fn update_blend_table(&mut self) {
+        self.blend_table = match self.blend_mode {
+            BlendMode::Authentic => Self::authentic_blend_table(),
+            BlendMode::Alpha => {
+                let palette = self.renderer.palette();
+                let channels = |rgb: u32| [(rgb >> 16) & 0xFF, (rgb >> 8) & 0xFF, rgb & 0xFF];
+                std::array::from_fn(|color| {
+                    let under = channels(palette[color]);
+                    let shaded = channels(palette[color | 0x08]);
+                    let target: [u32; 3] = std::array::from_fn(|i| (under[i] + shaded[i]) / 2);
+                    (0..NUM_COLORS)
+                        .min_by_key(|&candidate| {
+                            let rgb = channels(palette[candidate]);
+                            (0..3)
+                                .map(|i| rgb[i].abs_diff(target[i]).pow(2))
+                                .sum::<u32>()
+                        })
+                        .unwrap_or(color) as u8
+                })
+            }
+        };
+    }
+
+    fn blend_pair(&self, pixel_pair: u8, mask: u8) -> u8 {
+        let high = self.blend_table[(pixel_pair >> 4) as usize] << 4;
+        let low = self.blend_table[(pixel_pair & 0x0F) as usize];
+        (pixel_pair & !mask) | ((high | low) & mask)
+    }
+
     fn draw_point(&mut self, x: i16, y: i16, color: u8) {
-        if !(0..=319).contains(&x) || !(0..=199).contains(&y) {
+        if !(0..self.width).contains(&x) || !(0..self.height).contains(&y) {
             return;
         }
-        let offset: usize = (y * 160 + x / 2) as usize;
-        let (mut old_color_mask, mut new_color_mask): (u8, u8) = if x & 1 != 0 {
+        self.edges[self.work_buffer][y as usize * self.width as usize + x as usize] = 0;
+        let offset = y as usize * self.stride() + x as usize / 2;
+        let (old_color_mask, new_color_mask): (u8, u8) = if x & 1 != 0 {
             (0xF0, 0x0F)
         } else {
             (0x0F, 0xF0)
         };
-        let mut byte_color = color << 4 | color;
-        if color == 0x10 {
-            new_color_mask &= 0x88;
-            old_color_mask = !new_color_mask;
-            byte_color = 0x88;
-        } else if color == 0x11 {
-            byte_color = self.pages[0][offset];
-        }
         let pixel_pair = self.pages[self.work_buffer][offset];
-        self.pages[self.work_buffer][offset] =
-            (pixel_pair & old_color_mask) | (byte_color & new_color_mask);
+        self.pages[self.work_buffer][offset] = match color {
+            0x10 => self.blend_pair(pixel_pair, new_color_mask),
+            0x11 => {
+                let bg_pair = self.pages[self.background_buffer][offset];
+                (pixel_pair & old_color_mask) | (bg_pair & new_color_mask)
+            }
+            _ => (pixel_pair & old_color_mask) | ((color << 4 | color) & new_color_mask),
+        };
+    }
+
+    /// Offsets from the start of a line of the bytes it covers, each with
+    /// the mask of its nibbles that lie within `x_min..=x_max`. An odd
+    /// `x_min` only covers the low nibble of its byte and an even `x_max`
+    /// only the high one, as in the original.
+    fn line_masks(x_min: i16, x_max: i16) -> impl Iterator<Item = (usize, u8)> {
+        let last = (x_max / 2 - x_min / 2) as usize;
+        (0..=last).map(move |i| {
+            let mut mask = 0xFF;
+            if i == 0 && x_min & 1 != 0 {
+                mask &= 0x0F;
+            }
+            if i == last && x_max & 1 == 0 {
+                mask &= 0xF0;
+            }
+            (i, mask)
+        })
+    }
+
+    /// Forgets the edges of pixels `x_min..=x_max` on the current scanline
+    /// of the work page, which a span is painting over.
+    fn clear_edges(&mut self, x_min: i16, x_max: i16) {
+        let row = self.hline_y as usize * self.width as usize;
+        self.edges[self.work_buffer][row + x_min as usize..=row + x_max as usize].fill(0);
+    }
+
+    /// Records how much of the end pixels of the span just drawn the
+    /// polygon really covers, from the fractional part of its edges. The
+    /// left edge starts biased by half a pixel and the right one by a half
+    /// past it, so a pixel is fully covered up to that half.
+    fn mark_edges(&mut self, x_left: i16, x_right: i16, cpt_left: u32, cpt_right: u32) {
+        let edge = |covered: u32, side: u8| {
+            let coverage = (min(covered, 0x10000) * u32::from(EDGE_COVERAGE)) >> 16;
+            match coverage as u8 {
+                EDGE_COVERAGE => 0,
+                coverage => coverage | side,
+            }
+        };
+        let (width, row) = (self.width, self.hline_y as usize * self.width as usize);
+        let edges = &mut self.edges[self.work_buffer];
+        if (0..width).contains(&x_right) && x_right > x_left {
+            edges[row + x_right as usize] = edge(0x8000 + (cpt_right & 0xFFFF), EDGE_RIGHT);
+        }
+        if (0..width).contains(&x_left) && x_left <= x_right {
+            edges[row + x_left as usize] = edge(0x18000 - (cpt_left & 0xFFFF), 0);
+        }
     }
 
     fn draw_line_normal(&mut self, x1: i16, x2: i16, color: u8) {
         let x_max = max(x1, x2);
         let x_min = min(x1, x2);
-        let offset = (self.hline_y * 160 + x_min / 2) as usize;
-        let width = (x_max / 2 - x_min / 2 + 1) as usize;
+        self.clear_edges(x_min, x_max);
+        let offset = self.line_offset(x_min);
         let page = &mut self.pages[self.work_buffer];
         let byte_color = ((color & 0xF) << 4) | (color & 0xF);
-        page[offset] = (page[offset] & 0xF0) | (byte_color & 0x0F);
-        page[offset + width - 1] = (page[offset + width - 1] & 0x0F) | (byte_color & 0xF0);
-        let start = (x_min & 1) as usize;
-        let end = max(0, width as i16 - 1 - ((x_max & 1) ^ 1)) as usize;
-        (start..=end).for_each(|i| {
-            page[offset + i] = byte_color;
-        });
+        for (i, mask) in Self::line_masks(x_min, x_max) {
+            page[offset + i] = (page[offset + i] & !mask) | (byte_color & mask);
+        }
+    }
+
+    fn draw_vertical_line(&mut self, x: i16, y1: i16, y2: i16, color: u8) {
+        for y in max(y1, 0)..=min(y2, self.height - 1) {
+            self.draw_point(x, y, color);
+        }
+    }
+
+    fn draw_bounding_box(&mut self, pt: Point, bbw: i16, bbh: i16) {
+        let (x1, x2) = (pt.x - bbw / 2, pt.x + bbw / 2);
+        let (y1, y2) = (pt.y - bbh / 2, pt.y + bbh / 2);
+        if x1 >= self.width || x2 < 0 || y1 >= self.height || y2 < 0 {
+            return;
+        }
+        let hline_y = self.hline_y;
+        for y in [y1, y2] {
+            if (0..self.height).contains(&y) {
+                self.hline_y = y;
+                self.draw_line_normal(max(x1, 0), min(x2, self.width - 1), BOUNDING_BOX_COLOR);
+            }
+        }
+        self.hline_y = hline_y;
+        self.draw_vertical_line(x1, y1, y2, BOUNDING_BOX_COLOR);
+        self.draw_vertical_line(x2, y1, y2, BOUNDING_BOX_COLOR);
     }
 
     fn draw_line_from_bg(&mut self, x1: i16, x2: i16) {
         let x_max = max(x1, x2);
         let x_min = min(x1, x2);
-        let offset = (self.hline_y * 160 + x_min / 2) as usize;
-        let width = (x_max / 2 - x_min / 2 + 1) as usize;
-        let (left, right) = self.pages.split_at_mut(self.work_buffer);
-        let bg_page = left[0];
-        let work_page = &mut right[0];
-
-        work_page[offset] = (work_page[offset] & 0xF0) | (bg_page[offset] & 0x0F);
-        work_page[offset + width - 1] =
-            (work_page[offset + width - 1] & 0x0F) | (bg_page[offset + width - 1] & 0xF0);
-        let start = (x_min & 1) as usize;
-        let end = max(0, width as i16 - 1 - ((x_max & 1) ^ 1)) as usize;
-        (start..=end).for_each(|i| {
-            work_page[offset + i] = bg_page[offset + i];
-        });
+        self.clear_edges(x_min, x_max);
+        let offset = self.line_offset(x_min);
+        let Some((bg_page, work_page)) =
+            self.two_pages_mut(self.background_buffer, self.work_buffer)
+        else {
+            return;
+        };
+        for (i, mask) in Self::line_masks(x_min, x_max) {
+            work_page[offset + i] = (work_page[offset + i] & !mask) | (bg_page[offset + i] & mask);
+        }
     }
 
     fn draw_line_blend(&mut self, x1: i16, x2: i16) {
         let x_max = max(x1, x2);
         let x_min = min(x1, x2);
-        let offset = (self.hline_y * 160 + x_min / 2) as usize;
-        let width = (x_max / 2 - x_min / 2 + 1) as usize;
-        let page = &mut self.pages[self.work_buffer];
-        page[offset] |= 0x08;
-        page[offset + width - 1] |= 0x80;
-        let start = (x_min & 1) as usize;
-        let end = max(0, width as i16 - 1 - ((x_max & 1) ^ 1)) as usize;
-        (start..=end).for_each(|i| {
-            page[offset + i] |= 0x88;
-        });
+        self.clear_edges(x_min, x_max);
+        let offset = self.line_offset(x_min);
+        let work_buffer = self.work_buffer;
+        for (i, mask) in Self::line_masks(x_min, x_max) {
+            self.pages[work_buffer][offset + i] =
+                self.blend_pair(self.pages[work_buffer][offset + i], mask);
+        }
     }
 
     pub fn draw_string(&mut self, color: u8, x: u16, y: u16, string_id: u16) {
         let mut curr_x = x;
         let mut curr_y = y;
-        let string = STRING_TABLE[&string_id];
-        for &char in string.iter() {
-            curr_x += 1;
+        for &char in lookup_string(self.language, string_id) {
             if char == b'\x0A' {
                 curr_x = x;
                 curr_y += 8;
                 continue;
             }
 
-            self.draw_char(char, curr_x, curr_y, color);
+            // Columns are 8 pixels wide. Glyphs that would cross the right or
+            // bottom edge are dropped rather than wrapped around the page.
+            if (curr_x as usize) < WIDTH / 8 && curr_y as usize + 8 <= HEIGHT {
+                self.draw_char(char, curr_x, curr_y, color);
+            }
+            curr_x += 1;
         }
     }
 
     fn draw_char(&mut self, char: u8, x: u16, y: u16, color: u8) {
-        let font_offset = ((char - b' ') as u16 * 8) as usize;
-        let video_offset = (x * 4 + y * 160) as usize;
+        let scale = self.scale;
+        Self::draw_char_into(&mut self.pages[self.work_buffer], scale, char, x, y, color);
+        Self::clear_char_edges(&mut self.edges[self.work_buffer], scale, x, y);
+    }
+
+    /// Forgets the edges under the glyph cell at column `x` and row `y`.
+    fn clear_char_edges(edges: &mut [u8], scale: usize, x: u16, y: u16) {
+        let (left, top, size) = (x as usize * 8 * scale, y as usize * scale, 8 * scale);
+        for row in edges.chunks_mut(WIDTH * scale).skip(top).take(size) {
+            row[left..left + size].fill(0);
+        }
+    }
+
+    /// Draws `char` with its top left corner at column `x` (8 pixels wide)
+    /// and row `y` of the original resolution.
+    fn draw_char_into(page: &mut [u8], scale: usize, char: u8, x: u16, y: u16, color: u8) {
+        let Some(font_offset) = char
+            .checked_sub(b' ')
+            .map(|glyph| glyph as usize * 8)
+            .filter(|&offset| offset + 8 <= FONT.len())
+        else {
+            return;
+        };
+        if scale > 1 {
+            let stride = WIDTH * scale / 2;
+            let (left, top) = (x as usize * 8 * scale, y as usize * scale);
+            for (j, &font_row) in FONT[font_offset..font_offset + 8].iter().enumerate() {
+                for i in (0..8).filter(|i| font_row & (0x80 >> i) != 0) {
+                    Self::fill_block(
+                        page,
+                        stride,
+                        scale,
+                        left + i * scale,
+                        top + j * scale,
+                        color,
+                    );
+                }
+            }
+            return;
+        }
+        let video_offset = x as usize * 4 + y as usize * WIDTH / 2;
         for (j, &font_row) in FONT[font_offset..font_offset + 8].iter().enumerate() {
             let mut font_mask = font_row;
             for i in 0..4 {
-                let page = &mut self.pages[self.work_buffer];
                 let mut color_pair = 0;
                 let mut pixel_mask = 0xFF;
                 let has_left_pixel = font_mask & 0x80 != 0;
@@ -201,22 +494,158 @@ impl Video {
         }
     }
 
+    pub fn export_palette(&self) -> [u8; ACT_PALETTE_SIZE] {
+        self.renderer.export_palette()
+    }
+
+    pub fn import_palette(&mut self, data: &[u8]) -> Result<(), VideoError> {
+        self.renderer.import_palette(data)?;
+        self.update_blend_table();
+        Ok(())
+    }
+
+    pub fn set_color_vision(&mut self, color_vision: ColorVision) {
+        self.renderer.set_color_vision(color_vision);
+    }
+
+    pub fn set_palette_fade(&mut self, frames: usize) {
+        self.renderer.set_palette_fade(frames);
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.renderer.set_title(title);
+    }
+
+    pub fn toggle_draw_order(&mut self) {
+        self.draw_order = !self.draw_order;
+    }
+
+    /// Outlines the bounding box of every polygon drawn from now on.
+    pub fn toggle_bounding_boxes(&mut self) -> bool {
+        self.bounding_boxes = !self.bounding_boxes;
+        self.bounding_boxes
+    }
+
+    pub fn toggle_debug_palette(&mut self) {
+        self.debug_palette = !self.debug_palette;
+        self.renderer.set_debug_palette(self.debug_palette);
+    }
+
+    /// While locked, palette changes requested by the scripts are dropped
+    /// and the current palette stays on screen.
+    pub fn toggle_palette_lock(&mut self) -> bool {
+        self.palette_locked = !self.palette_locked;
+        self.palette_locked
+    }
+
+    pub fn set_overlay(&mut self, lines: Vec<String>) {
+        self.overlay = lines;
+    }
+
+    /// Sets the `size`x`size` pixels at `x`, `y` to `color`, clipped to the
+    /// page.
+    fn fill_block(page: &mut [u8], stride: usize, size: usize, x: usize, y: usize, color: u8) {
+        let rows = page.len() / stride;
+        for py in y..min(y + size, rows) {
+            for px in x..min(x + size, stride * 2) {
+                let pixel_pair = &mut page[py * stride + px / 2];
+                *pixel_pair = if px & 1 == 0 {
+                    (*pixel_pair & 0x0F) | (color << 4)
+                } else {
+                    (*pixel_pair & 0xF0) | (color & 0x0F)
+                };
+            }
+        }
+    }
+
+    fn compose_overlay(&self, page: &mut [u8], edges: &mut [u8]) {
+        let max_columns = WIDTH / 8;
+        let max_lines = HEIGHT / 8;
+        for (row, line) in self.overlay.iter().take(max_lines).enumerate() {
+            for (column, char) in line.bytes().take(max_columns).enumerate() {
+                if (b' '..=0x7F).contains(&char) {
+                    Self::draw_char_into(
+                        page,
+                        self.scale,
+                        char,
+                        column as u16,
+                        row as u16 * 8,
+                        OVERLAY_COLOR,
+                    );
+                    Self::clear_char_edges(edges, self.scale, column as u16, row as u16 * 8);
+                }
+            }
+        }
+    }
+
+    /// When disabled, blits still flip pages and apply palette changes but
+    /// nothing reaches the renderer.
+    pub fn set_present(&mut self, present: bool) {
+        self.present = present;
+    }
+
     pub fn change_working_buffer(&mut self, page_id: PageId) {
         self.work_buffer = self.get_page(page_id);
     }
 
+    /// Selects the page `copy_bg` decodes into and `draw_line_from_bg` reads
+    /// masked pixels from. The original keeps it on page 0.
+    pub fn set_background_buffer(&mut self, page_id: PageId) {
+        self.background_buffer = self.get_page(page_id);
+    }
+
+    /// Borrows page `src` for reading and page `dst` for writing at once, or
+    /// `None` when both are the same page.
+    fn two_pages_mut(&mut self, src: usize, dst: usize) -> Option<(&[u8], &mut [u8])> {
+        Self::pair_mut(&mut self.pages, src, dst)
+    }
+
+    /// `two_pages_mut` over any per-page buffers, pixels or edges.
+    fn pair_mut(pages: &mut [Vec<u8>; 4], src: usize, dst: usize) -> Option<(&[u8], &mut [u8])> {
+        match src.cmp(&dst) {
+            Ordering::Equal => None,
+            Ordering::Less => {
+                let (left, right) = pages.split_at_mut(dst);
+                Some((&left[src], &mut right[0]))
+            }
+            Ordering::Greater => {
+                let (left, right) = pages.split_at_mut(src);
+                Some((&right[0], &mut left[dst]))
+            }
+        }
+    }
+
+    /// `Front` and `Back` resolve to whichever page holds that role when
+    /// the opcode runs, as in the original. Filling `Back` and then blitting
+    /// `Back` therefore swaps the filled page to the front.
     fn get_page(&mut self, page_id: PageId) -> usize {
         match page_id {
             PageId::Front => self.front_buffer,
             PageId::Back => self.back_buffer,
-            PageId::Numbered(n) if n <= 3 => n as usize,
-            _ => panic!("Unsupported PageId pattern"),
+            PageId::Numbered(n) | PageId::NumberedScrolled(n) => (n & 3) as usize,
         }
     }
 
-    fn read_and_draw_polygon_hierarchy(
+    /// Draws the shape at `pt` in original coordinates, scaling the position
+    /// and zoom to the internal resolution.
+    pub fn read_and_draw_polygon<R: Read + Seek>(
         &mut self,
-        stream: &mut Cursor<Vec<u8>>,
+        stream: &mut R,
+        color: u8,
+        zoom: u16,
+        pt: Point,
+    ) -> Result<(), VideoError> {
+        let scale = self.scale as i16;
+        let pt = Point {
+            x: pt.x.saturating_mul(scale),
+            y: pt.y.saturating_mul(scale),
+        };
+        self.draw_polygon(stream, color, zoom.saturating_mul(scale as u16), pt)
+    }
+
+    fn read_and_draw_polygon_hierarchy<R: Read + Seek>(
+        &mut self,
+        stream: &mut R,
         zoom: u16,
         pgc: Point,
     ) -> Result<(), VideoError> {
@@ -237,20 +666,20 @@ impl Video {
 
             if bp & 0x8000 != 0 {
                 color = stream.read_u8()? & 0x7F;
-                stream.seek(io::SeekFrom::Current(1))?;
+                stream.seek(SeekFrom::Current(1))?;
             }
-            let bkp_offset = stream.position();
-            stream.set_position((offset * 2) as u64);
-            self.read_and_draw_polygon(stream, color, zoom, po)?;
+            let bkp_offset = stream.stream_position()?;
+            stream.seek(SeekFrom::Start((offset * 2) as u64))?;
+            self.draw_polygon(stream, color, zoom, po)?;
 
-            stream.set_position(bkp_offset);
+            stream.seek(SeekFrom::Start(bkp_offset))?;
         }
         Ok(())
     }
 
-    pub fn read_and_draw_polygon(
+    fn draw_polygon<R: Read + Seek>(
         &mut self,
-        stream: &mut Cursor<Vec<u8>>,
+        stream: &mut R,
         mut color: u8,
         zoom: u16,
         pt: Point,
@@ -261,7 +690,13 @@ impl Video {
                 color = command & 0x3F;
             }
             let polygon = Polygon::read_vertices(stream, zoom)?;
+            self.vertices_read += polygon.points.len();
             self.fill_polygon(color, pt, polygon);
+            if self.draw_order {
+                self.renderer
+                    .update_display_scaled(&self.pages[self.work_buffer], self.scale)?;
+                thread::sleep(DRAW_ORDER_DELAY);
+            }
         } else {
             let sub_command = command & 0x3F;
             match sub_command {
@@ -273,8 +708,32 @@ impl Video {
     }
 
     fn fill_polygon(&mut self, color: u8, pt: Point, polygon: Polygon) {
+        self.polygons_drawn += 1;
+        let bounding_box = self
+            .bounding_boxes
+            .then(|| (pt.clone(), polygon.bbw, polygon.bbh));
+        #[cfg(feature = "profile")]
+        let start = Instant::now();
+        self.rasterize_polygon(color, pt, polygon);
+        #[cfg(feature = "profile")]
+        self.profile.record_fill_polygon(start.elapsed());
+        if let Some((pt, bbw, bbh)) = bounding_box {
+            self.draw_bounding_box(pt, bbw, bbh);
+        }
+    }
+
+    fn rasterize_polygon(&mut self, color: u8, pt: Point, polygon: Polygon) {
         if polygon.bbw == 0 && polygon.bbh == 1 && polygon.points.len() == 4 {
-            self.draw_point(pt.x, pt.y, color);
+            self.vertices_filled += polygon.points.len();
+            let scale = self.scale as i16;
+            for y in pt.y..pt.y.saturating_add(scale) {
+                for x in pt.x..pt.x.saturating_add(scale) {
+                    self.draw_point(x, y, color);
+                }
+            }
+            if !self.quirks.contains(Quirk::SinglePointReturn) {
+                return;
+            }
         }
 
         let x1 = pt.x - polygon.bbw / 2;
@@ -282,91 +741,115 @@ impl Video {
         let y1 = pt.y - polygon.bbh / 2;
         let y2 = pt.y + polygon.bbh / 2;
 
-        if x1 > 319 || x2 < 0 || y1 > 199 || y2 < 0 {
+        if x1 >= self.width || x2 < 0 || y1 >= self.height || y2 < 0 {
+            return;
+        }
+
+        let points = &polygon.points;
+        if points.len() < 2 {
             return;
         }
+        self.vertices_filled += points.len();
 
+        // Edges are walked in 16.16 fixed point like the original, carrying
+        // the integer part of x from one edge to the next.
+        let (mut i, mut j) = (1, points.len() - 2);
+        let mut cpt_left = Self::to_fixed(points[j + 1].x + x1);
+        let mut cpt_right = Self::to_fixed(points[i - 1].x + x1);
         self.hline_y = y1;
-        for i in 0..polygon.points.len() / 2 {
-            let curr_left_p = &polygon.points[polygon.points.len() - 1 - i];
-            let next_left_p = &polygon.points[polygon.points.len() - 2 - i];
-            let curr_right_p = &polygon.points[i];
-            let next_right_p = &polygon.points[i + 1];
-
-            let step_left = self.calc_step(curr_left_p, next_left_p);
-            let step_right = self.calc_step(curr_right_p, next_right_p);
-            let h_diff = next_left_p.y - curr_left_p.y;
-
-            if h_diff > 0 {
-                let mut x_left = curr_left_p.x as f64 + x1 as f64;
-                let mut x_right = curr_right_p.x as f64 + x1 as f64;
-                for _ in 0..h_diff {
-                    if self.hline_y >= 0 && x_left <= 319.0 && x_right >= 0.0 {
-                        let mut draw_left = x_left.round() as i16;
-                        let mut draw_right = x_right.round() as i16;
-                        draw_left = max(0, draw_left);
-                        draw_right = min(draw_right, 319);
-                        match color {
-                            c if c < 0x10 => self.draw_line_normal(draw_left, draw_right, color),
-                            c if c > 0x10 => self.draw_line_from_bg(draw_left, draw_right),
-                            _ => self.draw_line_blend(draw_left, draw_right),
-                        }
+        for _ in 0..points.len() / 2 - 1 {
+            let (step_left, _) = Self::calc_step(&points[j + 1], &points[j]);
+            let (step_right, h) = Self::calc_step(&points[i - 1], &points[i]);
+            i += 1;
+            j = j.saturating_sub(1);
+            cpt_left = (cpt_left & 0xFFFF0000) | 0x7FFF;
+            cpt_right = (cpt_right & 0xFFFF0000) | 0x8000;
+
+            // Zero-height edges draw nothing but still move both sides to
+            // their end points before the next pair starts.
+            if h == 0 {
+                cpt_left = cpt_left.wrapping_add(step_left);
+                cpt_right = cpt_right.wrapping_add(step_right);
+                continue;
+            }
+            for _ in 0..h {
+                let x_left = (cpt_left >> 16) as u16 as i16;
+                let x_right = (cpt_right >> 16) as u16 as i16;
+                if self.hline_y >= 0 && x_left < self.width && x_right >= 0 {
+                    let draw_left = max(0, x_left);
+                    let draw_right = min(x_right, self.width - 1);
+                    #[cfg(feature = "profile")]
+                    let start = Instant::now();
+                    match color {
+                        c if c < 0x10 => self.draw_line_normal(draw_left, draw_right, color),
+                        c if c > 0x10 => self.draw_line_from_bg(draw_left, draw_right),
+                        _ => self.draw_line_blend(draw_left, draw_right),
                     }
-                    x_left += step_left;
-                    x_right += step_right;
-                    self.hline_y += 1;
-                    if self.hline_y > 199 {
-                        return;
+                    #[cfg(feature = "profile")]
+                    self.profile.record_draw_line(start.elapsed());
+                    if color < 0x10 {
+                        self.mark_edges(x_left, x_right, cpt_left, cpt_right);
                     }
                 }
+                cpt_left = cpt_left.wrapping_add(step_left);
+                cpt_right = cpt_right.wrapping_add(step_right);
+                self.hline_y += 1;
+                if self.hline_y >= self.height {
+                    return;
+                }
             }
         }
     }
 
     pub fn fill_page(&mut self, page_id: PageId, color: u8) {
-        let page = &mut self.pages[self.get_page(page_id)];
-        let byte_color = (color << 4) | color;
-        page.fill(byte_color);
+        let page = self.get_page(page_id);
+        self.pages[page].fill((color << 4) | color);
+        self.edges[page].fill(0);
     }
 
-    pub fn copy_page(&mut self, mut src_page_id: PageId, dst_page_id: PageId, vscroll: i16) {
-        if src_page_id == dst_page_id {
-            return;
-        }
-
-        let is_vertical_scrolled = matches!(src_page_id, PageId::Numbered(n) if n & 80 != 0);
-        let src_mask = if is_vertical_scrolled { 3 } else { 0xBF };
-        if let PageId::Numbered(n) = src_page_id {
-            src_page_id = PageId::Numbered(n & src_mask)
-        };
-
+    pub fn copy_page(&mut self, src_page_id: PageId, dst_page_id: PageId, vscroll: i16) {
+        let is_vertical_scrolled = matches!(src_page_id, PageId::NumberedScrolled(_))
+            && !self.quirks.contains(Quirk::CopyPageScroll);
         let (raw_src_page_id, raw_dst_page_id) =
             (self.get_page(src_page_id), self.get_page(dst_page_id));
-
-        let (src_page, dst_page) = if raw_src_page_id < raw_dst_page_id {
-            let (l, r) = self.pages.split_at_mut(raw_dst_page_id);
-            (&l[raw_src_page_id], &mut r[0])
+        let height = self.height as usize;
+        let rows_scrolled = if is_vertical_scrolled {
+            vscroll.unsigned_abs() as usize * self.scale
         } else {
-            let (l, r) = self.pages.split_at_mut(raw_src_page_id);
-            (&r[0], &mut l[raw_dst_page_id])
+            0
         };
-
-        if is_vertical_scrolled && vscroll.abs() <= 199 {
-            let data_to_copy = HEIGHT - vscroll.unsigned_abs() as usize;
-            let (src_offset, dst_offset) = if vscroll < 0 {
-                (-vscroll as usize, 0)
-            } else {
-                (0, vscroll as usize)
-            };
-            dst_page[dst_offset..dst_offset + data_to_copy]
-                .copy_from_slice(&src_page[src_offset..src_offset + data_to_copy]);
+        // Like the original, a scroll that moves the whole page out of view
+        // leaves the destination untouched.
+        if rows_scrolled >= height {
+            return;
+        }
+        let rows = height - rows_scrolled;
+        let (src_row, dst_row) = if vscroll < 0 {
+            (rows_scrolled, 0)
         } else {
-            dst_page.copy_from_slice(src_page);
+            (0, rows_scrolled)
+        };
+        // The edges move along with the pixels they belong to.
+        for (pages, row_len) in [
+            (&mut self.pages, self.width as usize / 2),
+            (&mut self.edges, self.width as usize),
+        ] {
+            let Some((src_page, dst_page)) =
+                Self::pair_mut(pages, raw_src_page_id, raw_dst_page_id)
+            else {
+                return;
+            };
+            dst_page[dst_row * row_len..(dst_row + rows) * row_len]
+                .copy_from_slice(&src_page[src_row * row_len..(src_row + rows) * row_len]);
         }
     }
 
+    /// Decodes a planar 320x200 bitmap into the background page, drawing each
+    /// source pixel as a block at higher internal resolutions.
     pub fn copy_bg(&mut self, src_data: &[u8]) {
-        let mut bg_page = self.pages[0];
+        let (scale, stride) = (self.scale, self.stride());
+        self.edges[self.background_buffer].fill(0);
+        let bg_page = &mut self.pages[self.background_buffer];
         for h in 0..HEIGHT {
             let bytes_per_row = WIDTH / 8;
             for w in 0..bytes_per_row {
@@ -384,52 +867,448 @@ impl Video {
                         acc |= ((planar_palette_idx[bit & 3] & 0x80) != 0) as u8;
                         planar_palette_idx[bit & 3] <<= 1;
                     }
-                    bg_page[h * bytes_per_row + w + byte] = acc;
+                    if scale == 1 {
+                        bg_page[h * WIDTH / 2 + w * 4 + byte] = acc;
+                        continue;
+                    }
+                    let x = (w * 8 + byte * 2) * scale;
+                    Self::fill_block(bg_page, stride, scale, x, h * scale, acc >> 4);
+                    Self::fill_block(bg_page, stride, scale, x + scale, h * scale, acc & 0x0F);
                 }
             }
         }
     }
 
-    fn calc_step(&self, p1: &Point, p2: &Point) -> f64 {
-        let dy = p2.y - p1.y;
-        let dx = p2.x - p1.x;
-        dx as f64 / dy as f64
+    fn to_fixed(x: i16) -> u32 {
+        ((x as i32) << 16) as u32
+    }
+
+    /// Fixed-point x increment per scanline from `p1` to `p2`, along with the
+    /// number of scanlines, computed with the original's integer math. A
+    /// horizontal edge divides by one instead of zero, so its step is the
+    /// whole `dx`, which moves the edge to its end point in a single addition.
+    fn calc_step(p1: &Point, p2: &Point) -> (u32, u16) {
+        let dy = p2.y.wrapping_sub(p1.y) as u16;
+        let delta = max(dy, 1) as i32;
+        let dx = p2.x as i32 - p1.x as i32;
+        (dx.wrapping_mul(0x4000 / delta).wrapping_shl(2) as u32, dy)
     }
 
     pub fn request_palette(&mut self, palette_request: PaletteRequest) {
+        if self.palette_locked {
+            return;
+        }
         self.palette_req = palette_request;
     }
 
-    fn change_palette(
+    fn change_palette<R: Read + Seek>(
         &mut self,
         palette_id: u8,
-        palette_segment: &mut Cursor<Vec<u8>>,
+        palette_segment: &mut R,
     ) -> Result<(), VideoError> {
         if palette_id >= 32 {
             return Err(VideoError::InvalidPalette(palette_id));
         }
-        palette_segment.seek(io::SeekFrom::Start(palette_id as u64 * 32))?;
-        Ok(self.renderer.set_palette(palette_segment)?)
+        palette_segment.seek(SeekFrom::Start(palette_id as u64 * 32))?;
+        self.renderer.set_palette(palette_segment)?;
+        self.palette_id = Some(palette_id);
+        if self.blend_mode == BlendMode::Alpha {
+            self.update_blend_table();
+        }
+        Ok(())
     }
 
-    pub fn update_display(
+    pub fn update_display<R: Read + Seek>(
         &mut self,
         page_id: PageId,
-        palette_segment: &mut Cursor<Vec<u8>>,
+        palette_segment: &mut R,
     ) -> Result<(), VideoError> {
-        if matches!(page_id, PageId::Numbered(_)) {
-            self.front_buffer = self.get_page(page_id);
-        } else if matches!(page_id, PageId::Back) {
-            (self.front_buffer, self.back_buffer) = (self.back_buffer, self.front_buffer);
+        match page_id {
+            PageId::Front => {}
+            PageId::Back => {
+                (self.front_buffer, self.back_buffer) = (self.back_buffer, self.front_buffer)
+            }
+            PageId::Numbered(_) | PageId::NumberedScrolled(_) => {
+                self.front_buffer = self.get_page(page_id)
+            }
         }
 
         if let PaletteRequest::Change(palette_id) = self.palette_req {
             self.change_palette(palette_id, palette_segment)?;
             self.palette_req = PaletteRequest::Keep;
         }
+        self.frames_presented += 1;
+        self.track_uniform_frames();
+        #[cfg(feature = "profile")]
+        self.profile.end_frame();
+        if !self.present {
+            return Ok(());
+        }
+        self.redisplay()
+    }
+
+    /// Remembers since which frame the front page has shown a single color.
+    fn track_uniform_frames(&mut self) {
+        let page = &self.pages[self.front_buffer];
+        let pixel_pair = page[0];
+        let uniform = pixel_pair >> 4 == pixel_pair & 0x0F && page.iter().all(|&b| b == pixel_pair);
+        self.uniform_since = match (uniform, self.uniform_since) {
+            (false, _) => None,
+            (true, None) => Some(self.frames_presented),
+            (true, since) => since,
+        };
+    }
+
+    /// Frame from which every presented frame has been a single color, if
+    /// the current one is.
+    pub fn uniform_since(&self) -> Option<usize> {
+        self.uniform_since
+    }
+
+    pub fn polygons_drawn(&self) -> usize {
+        self.polygons_drawn
+    }
+
+    /// Vertices of every polygon read so far, drawn or not.
+    pub fn vertices_read(&self) -> usize {
+        self.vertices_read
+    }
+
+    /// Vertices of the polygons that made it past clipping into the
+    /// rasterizer.
+    pub fn vertices_filled(&self) -> usize {
+        self.vertices_filled
+    }
+
+    /// The displayed page at the original 320x200 resolution.
+    pub fn front_page(&self) -> Cow<'_, [u8]> {
+        let page = &self.pages[self.front_buffer];
+        if self.scale == 1 {
+            Cow::Borrowed(page)
+        } else {
+            Cow::Owned(downscale(page, self.scale))
+        }
+    }
+
+    pub fn palette(&self) -> [u32; NUM_COLORS] {
+        self.renderer.palette()
+    }
+
+    /// Color index and resolved RGB of the displayed pixel at `x`, `y` of
+    /// the original resolution.
+    pub fn pixel_at(&self, x: usize, y: usize) -> (u8, u32) {
+        let (x, y) = (x * self.scale, y * self.scale);
+        let pixel_pair = self.pages[self.front_buffer][y * self.stride() + x / 2];
+        let index = if x & 1 == 0 {
+            pixel_pair >> 4
+        } else {
+            pixel_pair & 0x0F
+        };
+        (index, self.renderer.palette()[index as usize])
+    }
+
+    pub fn save_state(&self) -> VideoState {
+        VideoState {
+            pages: self.pages.clone(),
+            work_buffer: self.work_buffer,
+            background_buffer: self.background_buffer,
+            front_buffer: self.front_buffer,
+            back_buffer: self.back_buffer,
+            palette_id: self.palette_id,
+        }
+    }
+
+    /// Bytes per page at the current internal scale.
+    pub fn page_size(&self) -> usize {
+        self.pages[0].len()
+    }
 
-        Ok(self
-            .renderer
-            .update_display(&self.pages[self.front_buffer])?)
+    pub fn load_state(&mut self, state: &VideoState) {
+        self.pages.clone_from(&state.pages);
+        self.edges.iter_mut().for_each(|edges| edges.fill(0));
+        self.work_buffer = state.work_buffer;
+        self.background_buffer = state.background_buffer;
+        self.front_buffer = state.front_buffer;
+        self.back_buffer = state.back_buffer;
+    }
+
+    pub fn redisplay(&mut self) -> Result<(), VideoError> {
+        if self.overlay.is_empty() {
+            self.renderer.set_edges(&self.edges[self.front_buffer]);
+            return Ok(self
+                .renderer
+                .update_display_scaled(&self.pages[self.front_buffer], self.scale)?);
+        }
+        let mut frame = self.pages[self.front_buffer].clone();
+        let mut edges = self.edges[self.front_buffer].clone();
+        self.compose_overlay(&mut frame, &mut edges);
+        self.renderer.set_edges(&edges);
+        Ok(self.renderer.update_display_scaled(&frame, self.scale)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessRenderer;
+
+    const UNTOUCHED: u8 = 0xFF;
+
+    fn video() -> Video {
+        Video::new(Box::new(HeadlessRenderer::default()))
+    }
+
+    /// Fills every row of page 0 with its row number and page 1 with
+    /// `UNTOUCHED`.
+    fn scroll_video() -> Video {
+        let mut video = video();
+        let stride = video.stride();
+        for (y, row) in video.pages[0].chunks_mut(stride).enumerate() {
+            row.fill(y as u8);
+        }
+        video.pages[1].fill(UNTOUCHED);
+        video
+    }
+
+    /// First byte of each row of `page`, after checking each row is uniform.
+    fn rows(video: &Video, page: usize) -> Vec<u8> {
+        video.pages[page]
+            .chunks(video.stride())
+            .map(|row| {
+                assert!(row.iter().all(|&b| b == row[0]));
+                row[0]
+            })
+            .collect()
+    }
+
+    fn scrolled_rows(vscroll: i16) -> Vec<u8> {
+        let mut video = scroll_video();
+        video.copy_page(PageId::from(0x80), PageId::Numbered(1), vscroll);
+        rows(&video, 1)
+    }
+
+    /// Rows expected on page 1 after copying page 0 scrolled by `vscroll`.
+    fn expected_rows(vscroll: i16) -> Vec<u8> {
+        (0..HEIGHT as i16)
+            .map(|y| match y - vscroll {
+                src @ 0..200 => src as u8,
+                _ => UNTOUCHED,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn copy_page_scrolls_up_and_down() {
+        for vscroll in [-10, 10] {
+            assert_eq!(scrolled_rows(vscroll), expected_rows(vscroll), "{vscroll}");
+        }
+    }
+
+    fn polygon(bbw: i16, bbh: i16, points: &[(i16, i16)]) -> Polygon {
+        Polygon {
+            bbw,
+            bbh,
+            points: points.iter().map(|&(x, y)| Point { x, y }).collect(),
+        }
+    }
+
+    /// Coordinates of the non-zero pixels of the work buffer, row by row.
+    fn painted(video: &Video) -> Vec<(i16, i16)> {
+        let page = &video.pages[video.work_buffer];
+        (0..video.height)
+            .flat_map(|y| (0..video.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                let pair = page[y as usize * video.stride() + x as usize / 2];
+                let color = if x & 1 == 0 { pair >> 4 } else { pair & 0x0F };
+                color != 0
+            })
+            .collect()
+    }
+
+    /// Expands `(y, x_min, x_max)` spans into the pixels they cover.
+    fn spans(rows: &[(i16, i16, i16)]) -> Vec<(i16, i16)> {
+        rows.iter()
+            .flat_map(|&(y, x_min, x_max)| (x_min..=x_max).map(move |x| (x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn rasterizes_triangle_like_the_original() {
+        let mut video = video();
+        let triangle = polygon(4, 4, &[(2, 0), (4, 4), (0, 4), (2, 0)]);
+        video.rasterize_polygon(5, Point { x: 2, y: 2 }, triangle);
+        assert_eq!(
+            painted(&video),
+            spans(&[(0, 2, 2), (1, 1, 3), (2, 1, 3), (3, 0, 4)])
+        );
+    }
+
+    #[test]
+    fn zero_height_edge_pair_moves_both_sides() {
+        let mut video = video();
+        // The right side steps out from x=2 to x=4 halfway down.
+        #[rustfmt::skip]
+        let step = polygon(4, 4, &[
+            (2, 0), (2, 2), (4, 2), (4, 4),
+            (0, 4), (0, 2), (0, 2), (0, 0),
+        ]);
+        video.rasterize_polygon(5, Point { x: 2, y: 2 }, step);
+        assert_eq!(
+            painted(&video),
+            spans(&[(0, 0, 2), (1, 0, 2), (2, 0, 4), (3, 0, 4)])
+        );
+    }
+
+    /// `(x, y, edge)` of every pixel of the work page on a polygon edge.
+    fn edges(video: &Video) -> Vec<(i16, i16, u8)> {
+        let width = video.width as usize;
+        video.edges[video.work_buffer]
+            .iter()
+            .enumerate()
+            .filter(|&(_, &edge)| edge != 0)
+            .map(|(i, &edge)| ((i % width) as i16, (i / width) as i16, edge))
+            .collect()
+    }
+
+    #[test]
+    fn slanted_polygon_marks_only_span_ends() {
+        let mut video = video();
+        let triangle = polygon(4, 4, &[(2, 0), (4, 4), (0, 4), (2, 0)]);
+        video.rasterize_polygon(5, Point { x: 2, y: 2 }, triangle);
+        let rows = [(0, 2, 2), (1, 1, 3), (2, 1, 3), (3, 0, 4)];
+        let edges = edges(&video);
+        assert!(!edges.is_empty());
+        for (x, y, edge) in edges {
+            let &(_, x_min, x_max) = rows.iter().find(|row| row.0 == y).unwrap();
+            match edge & EDGE_RIGHT {
+                0 => assert_eq!(x, x_min, "left edge at {x},{y}"),
+                _ => assert_eq!(x, x_max, "right edge at {x},{y}"),
+            }
+        }
+    }
+
+    #[test]
+    fn edges_follow_fills_and_page_copies() {
+        let mut video = video();
+        let triangle = polygon(4, 4, &[(2, 0), (4, 4), (0, 4), (2, 0)]);
+        video.rasterize_polygon(5, Point { x: 2, y: 2 }, triangle);
+        assert!(!edges(&video).is_empty());
+        video.copy_page(PageId::Numbered(2), PageId::Numbered(1), 0);
+        assert_eq!(video.edges[1], video.edges[2]);
+        video.copy_page(PageId::from(0x82), PageId::Numbered(3), 3);
+        let width = video.width as usize;
+        assert_eq!(video.edges[3][3 * width..], video.edges[2][..197 * width]);
+        video.fill_page(PageId::Numbered(2), 1);
+        assert!(edges(&video).is_empty());
+    }
+
+    #[test]
+    fn single_point_changes_one_pixel_with_and_without_quirk() {
+        // Only the quirk goes on to the polygon fill, which counts the
+        // vertices a second time.
+        for (quirks, vertices_filled) in [
+            (Quirks::default(), 4),
+            (Quirks::new(&[Quirk::SinglePointReturn]), 8),
+        ] {
+            let mut video = video();
+            video.set_quirks(quirks);
+            let point = polygon(0, 1, &[(0, 0), (0, 1), (0, 1), (0, 0)]);
+            video.rasterize_polygon(5, Point { x: 7, y: 3 }, point);
+            assert_eq!(painted(&video), [(7, 3)], "{quirks:?}");
+            assert_eq!(video.vertices_filled(), vertices_filled, "{quirks:?}");
+        }
+    }
+
+    #[test]
+    fn copy_page_scroll_past_the_page_leaves_it_untouched() {
+        for vscroll in [-250, -100, 0, 100, 250] {
+            assert_eq!(scrolled_rows(vscroll), expected_rows(vscroll), "{vscroll}");
+        }
+        for vscroll in [-250, 250] {
+            assert!(scrolled_rows(vscroll).iter().all(|&row| row == UNTOUCHED));
+        }
+    }
+
+    #[test]
+    fn update_display_picks_the_front_page() {
+        let mut video = video();
+        let mut palette = io::Cursor::new(Vec::new());
+        assert_eq!((video.front_buffer, video.back_buffer), (2, 1));
+
+        video.update_display(PageId::Front, &mut palette).unwrap();
+        assert_eq!((video.front_buffer, video.back_buffer), (2, 1));
+
+        video.update_display(PageId::Back, &mut palette).unwrap();
+        assert_eq!((video.front_buffer, video.back_buffer), (1, 2));
+
+        video
+            .update_display(PageId::Numbered(3), &mut palette)
+            .unwrap();
+        assert_eq!((video.front_buffer, video.back_buffer), (3, 2));
+    }
+
+    #[test]
+    fn two_pages_mut_borrows_the_right_pages() {
+        let mut video = video();
+        for (page, data) in video.pages.iter_mut().enumerate() {
+            data.fill(page as u8);
+        }
+        for (src, dst) in [(1, 2), (2, 1), (0, 3), (3, 0)] {
+            let (src_page, dst_page) = video.two_pages_mut(src, dst).unwrap();
+            assert_eq!((src_page[0], dst_page[0]), (src as u8, dst as u8));
+        }
+        assert!(video.two_pages_mut(2, 2).is_none());
+    }
+
+    #[test]
+    fn calc_step_of_horizontal_edge_is_whole_dx() {
+        let step =
+            |x1, y1, x2, y2| Video::calc_step(&Point { x: x1, y: y1 }, &Point { x: x2, y: y2 });
+        assert_eq!(step(0, 3, 5, 3), (5 << 16, 0));
+        assert_eq!(step(5, 3, 2, 3), ((-3i32 << 16) as u32, 0));
+        assert_eq!(step(4, 3, 4, 3), (0, 0));
+        assert_eq!(step(2, 0, 4, 4), (0x8000, 4));
+    }
+
+    #[test]
+    fn blitting_back_shows_the_filled_back_page() {
+        let mut video = video();
+        video.fill_page(PageId::Back, 7);
+        video
+            .update_display(PageId::Back, &mut io::Cursor::new(Vec::new()))
+            .unwrap();
+        assert_eq!(video.pixel_at(0, 0).0, 7);
+        assert!(video.pages[video.front_buffer].iter().all(|&b| b == 0x77));
+    }
+
+    #[test]
+    fn point_and_span_copy_from_the_background_page() {
+        let mut video = video();
+        video.set_background_buffer(PageId::Numbered(3));
+        video.fill_page(PageId::Numbered(3), 9);
+        video.draw_point(1, 0, 0x11);
+        video.hline_y = 2;
+        video.draw_line_from_bg(4, 6);
+        assert_eq!(painted(&video), [(1, 0), (4, 2), (5, 2), (6, 2)]);
+    }
+
+    #[test]
+    fn draw_string_starts_at_the_given_column() {
+        let mut video = video();
+        // "A", then two lines of text.
+        video.draw_string(5, 1, 0, 0x006);
+        video.draw_string(5, 2, 16, 0x002);
+        let painted = painted(&video);
+        let first_column = |rows: std::ops::Range<i16>| {
+            painted
+                .iter()
+                .filter(|(_, y)| rows.contains(y))
+                .map(|&(x, _)| x)
+                .min()
+                .unwrap()
+        };
+        assert!((8..16).contains(&first_column(0..8)));
+        // The second line goes back to column 2.
+        assert!((16..24).contains(&first_column(24..32)));
     }
 }
@@ -1,46 +1,79 @@
 use std::{
-    cmp::max,
+    cmp::min,
     io::{self, Seek, SeekFrom},
-    thread,
-    time::{Duration, Instant},
 };
 
 use byteorder::{BigEndian, ReadBytesExt};
-use log::debug;
+use clap::ValueEnum;
+use log::{error, trace, warn};
 use num_enum::{TryFromPrimitive, TryFromPrimitiveError};
 use rand::random;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
+    audio::{AudioError, Module, MusicRequest, Sample, SoundRequest},
     channel::{Channel, ProcessCounter, State},
+    coverage::OpcodeCoverage,
+    disasm::{BackgroundOperands, SpriteOperands},
     execution_context::ExecutionContext,
+    input::InputState,
     loaded::LoadedAsset,
     opcodes::OPCODE_TABLE,
     parts::GamePart,
     resource::{NUM_MEM_ENTRIES, ResourceError},
     shapes::Point,
+    sound::frequency_for,
     video::{PageId, PaletteRequest, VideoError},
 };
 
 const NUM_CHANNELS: usize = 64;
 const NUM_VARIABLES: usize = 256;
+pub const DEFAULT_WATCHDOG_LIMIT: usize = 1_000_000;
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+const FIRST_PART_ID: u16 = GamePart::One as u16;
+const LAST_PART_ID: u16 = GamePart::Ten as u16;
+const VM_VARIABLE_MUSIC_MARK: usize = 0xF4;
 const VM_VARIABLE_SCROLL_Y: usize = 0xF9;
 const VM_VARIABLE_PAUSE_SLICES: usize = 0xFF;
+const VM_VARIABLE_RANDOM_SEED: usize = 0x3C;
+const VM_VARIABLE_HERO_POS_UP_DOWN: usize = 0xE5;
+const VM_VARIABLE_HERO_ACTION: usize = 0xFA;
+const VM_VARIABLE_HERO_POS_JUMP_DOWN: usize = 0xFB;
+const VM_VARIABLE_HERO_POS_LEFT_RIGHT: usize = 0xFC;
+const VM_VARIABLE_HERO_POS_MASK: usize = 0xFD;
+const VM_VARIABLE_HERO_ACTION_POS_MASK: usize = 0xFE;
 
 #[derive(Error, Debug)]
 pub enum VmError {
     #[error("IO error reading underlying stream")]
-    Io(io::Error),
-    #[error("Missing polygon segment")]
-    MissingPolygonSegment,
+    Io(#[source] io::Error),
     #[error("Invalid game part {0}")]
     InvalidGamePart(u16),
+    #[error("Resource id {0:#06X} is neither a memlist entry nor a game part")]
+    InvalidResource(u16),
     #[error("Stack underflow")]
     StackUnderflow,
+    #[error("Stack overflow")]
+    StackOverflow,
+    #[error("Unexpected comparison operator {0}")]
+    UnexpectedComparison(u8),
+    #[error("Invalid channel range {0}..={1}")]
+    InvalidChannelRange(usize, usize),
+    #[error("Invalid channel {0}, there are only {NUM_CHANNELS}")]
+    InvalidChannel(u8),
+    #[error("Bytecode offset {offset:#06X} is past the end of the {len} bytes segment")]
+    InvalidBytecodeOffset { offset: usize, len: usize },
+    #[error("Channel {0} ran too many opcodes without yielding")]
+    ChannelStalled(usize),
+    #[error("Unknown opcode {opcode:#04X} at offset {offset:#06X}")]
+    UnknownOpcode { opcode: u8, offset: u64 },
     #[error("Video error")]
-    VideoError(VideoError),
+    VideoError(#[source] VideoError),
     #[error("Resource error")]
-    ResourceError(ResourceError),
+    ResourceError(#[source] ResourceError),
+    #[error("Audio error")]
+    AudioError(#[source] AudioError),
 }
 
 impl From<io::Error> for VmError {
@@ -49,6 +82,12 @@ impl From<io::Error> for VmError {
     }
 }
 
+impl From<AudioError> for VmError {
+    fn from(value: AudioError) -> Self {
+        VmError::AudioError(value)
+    }
+}
+
 impl From<ResourceError> for VmError {
     fn from(value: ResourceError) -> Self {
         VmError::ResourceError(value)
@@ -67,40 +106,222 @@ impl From<TryFromPrimitiveError<GamePart>> for VmError {
     }
 }
 
+macro_rules! trace_op {
+    ($vm:expr, $name:expr) => {
+        trace_op!($vm, $name, "")
+    };
+    ($vm:expr, $name:expr, $($arg:tt)+) => {
+        trace!(
+            "ch={:02} pc={:#06X} {:<16} {}",
+            $vm.running_channel_id,
+            $vm.opcode_offset,
+            $name,
+            format_args!($($arg)+)
+        )
+    };
+}
+
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    #[default]
+    Abort,
+    SkipChannel,
+}
+
+#[derive(Serialize)]
+pub struct ChannelSnapshot {
+    pub id: usize,
+    pub state: State,
+    pub pc: Option<usize>,
+    pub next_pc: Option<usize>,
+}
+
+/// Human-readable view of the VM at a given moment, meant for inspection
+/// rather than for resuming execution.
+#[derive(Serialize)]
+pub struct VmSnapshot {
+    pub variables: Vec<i16>,
+    pub channels: Vec<ChannelSnapshot>,
+    pub running_channel_id: usize,
+    pub call_stack: Vec<u64>,
+}
+
+/// Work done during the last `host_frame`, to tell VM-heavy scenes from
+/// render-heavy ones.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameCounters {
+    pub opcodes: usize,
+    pub channels_run: usize,
+    pub draw_calls: usize,
+    pub polygons: usize,
+    pub vertices_read: usize,
+    pub vertices_filled: usize,
+}
+
+/// The part of the VM that changes from frame to frame, captured between
+/// two host frames when no channel is running and the call stack is empty.
+#[derive(Clone)]
+pub struct VmState {
+    pub(crate) variables: [i16; NUM_VARIABLES],
+    pub(crate) channels: [Channel; NUM_CHANNELS],
+    pub(crate) running_channel_id: usize,
+}
+
+impl Default for VmState {
+    fn default() -> Self {
+        Self {
+            variables: [0; NUM_VARIABLES],
+            channels: [Channel::default(); NUM_CHANNELS],
+            running_channel_id: 0,
+        }
+    }
+}
+
 pub struct Vm {
     variables: [i16; NUM_VARIABLES],
+    protection_bypass: [(usize, i16); 4],
     channels: [Channel; NUM_CHANNELS],
     running_channel_id: usize,
+    opcode_offset: u64,
     stack: Vec<u64>,
+    error_policy: ErrorPolicy,
+    watchdog_limit: usize,
+    max_call_depth: usize,
+    skip_unknown_opcodes: bool,
+    coverage: Option<OpcodeCoverage>,
+    preempt_budget: Option<usize>,
+    frame_counters: FrameCounters,
+    /// Whether a channel of the current part was killed after an error.
+    channels_failed: bool,
+}
+
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GameVersion {
+    #[default]
+    Dos,
+    Amiga,
+}
+
+/// Variables the original executable sets before the scripts start.
+#[derive(Copy, Clone, Debug)]
+pub struct VmConfig {
+    pub random_seed: i16,
+    /// Set to 0x81 by every release; its meaning is unknown.
+    pub var_0x54: i16,
+    /// The values the copy protection screens leave in 0xBC, 0xC6, 0xF2 and
+    /// 0xDC, which the later parts check.
+    pub protection_bypass: [(usize, i16); 4],
+}
+
+impl VmConfig {
+    pub fn for_version(version: GameVersion, random_seed: i16) -> Self {
+        let protection_counter = match version {
+            GameVersion::Dos => 4000,
+            GameVersion::Amiga => 6000,
+        };
+        Self {
+            random_seed,
+            var_0x54: 0x81,
+            protection_bypass: [
+                (0xBC, 0x10),
+                (0xC6, 0x80),
+                (0xF2, protection_counter),
+                (0xDC, 33),
+            ],
+        }
+    }
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self::for_version(GameVersion::Dos, random::<i16>())
+    }
 }
 
 impl Default for Vm {
     fn default() -> Self {
+        Self::new(VmConfig::default())
+    }
+}
+
+impl Vm {
+    pub fn new(config: VmConfig) -> Self {
         let mut variables = [0; NUM_VARIABLES];
-        variables[0x54] = 0x81;
-        variables[0x3C] = random::<i16>();
-        variables[0xBC] = 0x10;
-        variables[0xC6] = 0x80;
-        variables[0xF2] = 4000;
-        variables[0xDC] = 33;
+        variables[0x54] = config.var_0x54;
+        variables[VM_VARIABLE_RANDOM_SEED] = config.random_seed;
+        for (id, value) in config.protection_bypass {
+            variables[id] = value;
+        }
         let channels = [Channel::default(); NUM_CHANNELS];
         Self {
             variables,
             channels,
+            protection_bypass: config.protection_bypass,
             running_channel_id: 0,
+            opcode_offset: 0,
             stack: Vec::default(),
+            error_policy: ErrorPolicy::default(),
+            watchdog_limit: DEFAULT_WATCHDOG_LIMIT,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            skip_unknown_opcodes: false,
+            coverage: None,
+            preempt_budget: None,
+            frame_counters: FrameCounters::default(),
+            channels_failed: false,
         }
     }
-}
 
-impl Vm {
     pub fn init_part(&mut self) -> Result<(), VmError> {
         self.variables[0xE4] = 0x14;
         self.channels.iter_mut().for_each(Channel::reset);
         self.channels[0].set_pc(ProcessCounter::Valid(0));
+        self.channels_failed = false;
         Ok(())
     }
 
+    pub fn get_variable(&self, id: usize) -> i16 {
+        self.variables[id]
+    }
+
+    pub fn set_variable(&mut self, id: usize, value: i16) {
+        self.variables[id] = value;
+    }
+
+    pub fn apply_input(&mut self, input: InputState) {
+        let mut mask = 0;
+        let mut left_right = 0;
+        let mut up_down = 0;
+        if input.right {
+            left_right = 1;
+            mask |= 1;
+        }
+        if input.left {
+            left_right = -1;
+            mask |= 2;
+        }
+        if input.down {
+            up_down = 1;
+            mask |= 4;
+        }
+        if input.up {
+            up_down = -1;
+            mask |= 8;
+        }
+        self.variables[VM_VARIABLE_HERO_POS_UP_DOWN] = up_down;
+        self.variables[VM_VARIABLE_HERO_POS_JUMP_DOWN] = up_down;
+        self.variables[VM_VARIABLE_HERO_POS_LEFT_RIGHT] = left_right;
+        self.variables[VM_VARIABLE_HERO_POS_MASK] = mask;
+
+        let action = input.action as i16;
+        if input.action {
+            mask |= 0x80;
+        }
+        self.variables[VM_VARIABLE_HERO_ACTION] = action;
+        self.variables[VM_VARIABLE_HERO_ACTION_POS_MASK] = mask;
+    }
+
     pub fn check_channel_requests(&mut self) -> Result<(), VmError> {
         for channel_id in 0..NUM_CHANNELS {
             self.channels[channel_id].apply_next_pc();
@@ -109,6 +330,25 @@ impl Vm {
     }
 
     pub fn host_frame(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
+        self.frame_counters = FrameCounters::default();
+        if let Some(mark) = context.audio.take_music_mark() {
+            self.variables[VM_VARIABLE_MUSIC_MARK] = mark;
+        }
+        let video = &context.video;
+        let before = (
+            video.polygons_drawn(),
+            video.vertices_read(),
+            video.vertices_filled(),
+        );
+        let result = self.run_channels(context);
+        let video = &context.video;
+        self.frame_counters.polygons = video.polygons_drawn() - before.0;
+        self.frame_counters.vertices_read = video.vertices_read() - before.1;
+        self.frame_counters.vertices_filled = video.vertices_filled() - before.2;
+        result
+    }
+
+    fn run_channels(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         for channel_id in 0..NUM_CHANNELS {
             if self.channels[channel_id].state != State::Ready {
                 continue;
@@ -116,39 +356,174 @@ impl Vm {
 
             if let ProcessCounter::Valid(pc) = self.channels[channel_id].pc {
                 self.stack.clear();
-                self.run_channel(channel_id, pc, context)?
+                match self.run_channel(channel_id, pc, context) {
+                    Err(e) if self.error_policy == ErrorPolicy::SkipChannel => {
+                        error!("Channel {channel_id} failed and has been killed: {e:?}");
+                        self.channels[channel_id].reset();
+                        self.channels_failed = true;
+                    }
+                    result => result?,
+                }
             }
         }
         Ok(())
     }
 
+    pub fn frame_counters(&self) -> FrameCounters {
+        self.frame_counters
+    }
+
+    pub fn ready_channels(&self) -> usize {
+        self.channels
+            .iter()
+            .filter(|channel| {
+                channel.state == State::Ready && matches!(channel.pc, ProcessCounter::Valid(_))
+            })
+            .count()
+    }
+
+    /// True when no channel is ready and none has a pending `set_next_pc`.
+    pub fn is_halted(&self) -> bool {
+        self.ready_channels() == 0
+            && self
+                .channels
+                .iter()
+                .all(|channel| channel.next_pc.is_none())
+    }
+
+    /// Whether `ErrorPolicy::SkipChannel` killed a channel since the part
+    /// was set up, in which case a halt is not the script's own doing.
+    pub fn has_failed_channels(&self) -> bool {
+        self.channels_failed
+    }
+
+    pub fn dump_debug(&self) -> VmSnapshot {
+        VmSnapshot {
+            variables: self.variables.to_vec(),
+            channels: self
+                .channels
+                .iter()
+                .enumerate()
+                .map(|(id, channel)| ChannelSnapshot {
+                    id,
+                    state: channel.state,
+                    pc: channel.pc.offset(),
+                    next_pc: channel.next_pc.and_then(ProcessCounter::offset),
+                })
+                .collect(),
+            running_channel_id: self.running_channel_id,
+            call_stack: self.stack.clone(),
+        }
+    }
+
+    pub fn save_state(&self) -> VmState {
+        VmState {
+            variables: self.variables,
+            channels: self.channels,
+            running_channel_id: self.running_channel_id,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &VmState) {
+        self.variables = state.variables;
+        self.channels = state.channels;
+        self.running_channel_id = state.running_channel_id;
+        self.stack.clear();
+    }
+
+    pub fn set_protection_bypass(&mut self, bypass: bool) {
+        for (id, value) in self.protection_bypass {
+            self.variables[id] = if bypass { value } else { 0 };
+        }
+    }
+
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.error_policy = error_policy;
+    }
+
+    pub fn set_watchdog_limit(&mut self, watchdog_limit: usize) {
+        self.watchdog_limit = watchdog_limit;
+    }
+
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Logs opcodes past the end of `OPCODE_TABLE` and moves on to the next
+    /// byte instead of failing. Their operands are unknown, so what follows
+    /// may decode as garbage; this is only for best-effort playback of
+    /// modified data.
+    pub fn set_skip_unknown_opcodes(&mut self, skip: bool) {
+        self.skip_unknown_opcodes = skip;
+    }
+
+    /// Forces a yield once a channel has executed `budget` opcodes in a single
+    /// run, as long as it is not inside a subroutine. `None` keeps the
+    /// original cooperative scheduling.
+    pub fn set_preempt_budget(&mut self, budget: Option<usize>) {
+        self.preempt_budget = budget.filter(|&budget| budget > 0);
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(OpcodeCoverage::default);
+    }
+
+    pub fn coverage(&self) -> Option<&OpcodeCoverage> {
+        self.coverage.as_ref()
+    }
+
     fn run_channel(
         &mut self,
         channel_id: usize,
         channel_pc: usize,
         context: &mut ExecutionContext,
     ) -> Result<(), VmError> {
-        context
-            .loaded_part
-            .bytecode
-            .seek(SeekFrom::Start(channel_pc as u64))?;
+        context.loaded_part.seek_bytecode(channel_pc)?;
 
         self.running_channel_id = channel_id;
         self.channels[channel_id].state = State::Running;
-        loop {
+        self.frame_counters.channels_run += 1;
+        for executed in 0..self.watchdog_limit {
+            if self.preempt_budget.is_some_and(|budget| executed >= budget) && self.stack.is_empty()
+            {
+                let execution_pc = context.loaded_part.bytecode.position().into();
+                self.channels[channel_id].yield_control(execution_pc);
+                trace!("ch={channel_id:02} preempted after {executed} opcodes");
+                return Ok(());
+            }
+            self.opcode_offset = context.loaded_part.bytecode.position();
             let opcode = context.loaded_part.bytecode.read_u8()?;
+            self.frame_counters.opcodes += 1;
+            if let Some(coverage) = self.coverage.as_mut() {
+                coverage.record(opcode);
+            }
+            if opcode & 0xC0 != 0 {
+                self.frame_counters.draw_calls += 1;
+            }
             match opcode {
                 opcode if opcode & 0x80 != 0 => self.draw_background(opcode, context)?,
                 opcode if opcode & 0x40 != 0 => self.draw_sprite(opcode, context)?,
-                _ => OPCODE_TABLE[opcode as usize](self, context)?,
+                _ => match OPCODE_TABLE.get(opcode as usize) {
+                    Some(op) => op(self, context)?,
+                    None if self.skip_unknown_opcodes => warn!(
+                        "Skipping unknown opcode {opcode:#04X} at offset {:#06X}",
+                        self.opcode_offset
+                    ),
+                    None => {
+                        return Err(VmError::UnknownOpcode {
+                            opcode,
+                            offset: self.opcode_offset,
+                        });
+                    }
+                },
             };
 
             if self.channels[channel_id].state != State::Running {
-                break;
+                return Ok(());
             }
         }
 
-        Ok(())
+        Err(VmError::ChannelStalled(channel_id))
     }
 
     pub fn op_mov_const(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
@@ -156,6 +531,7 @@ impl Vm {
         let variable_id = bytecode.read_u8()? as usize;
         let value = bytecode.read_u16::<BigEndian>()? as i16;
         self.variables[variable_id] = value;
+        trace_op!(self, "mov_const", "var[{variable_id:#04X}] = {value}");
         Ok(())
     }
 
@@ -164,6 +540,12 @@ impl Vm {
         let dst_variable_id = bytecode.read_u8()? as usize;
         let src_variable_id = bytecode.read_u8()? as usize;
         self.variables[dst_variable_id] = self.variables[src_variable_id];
+        trace_op!(
+            self,
+            "mov",
+            "var[{dst_variable_id:#04X}] = var[{src_variable_id:#04X}] -> {}",
+            self.variables[dst_variable_id]
+        );
         Ok(())
     }
 
@@ -171,7 +553,14 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let dst_variable_id = bytecode.read_u8()? as usize;
         let src_variable_id = bytecode.read_u8()? as usize;
-        self.variables[dst_variable_id] += self.variables[src_variable_id];
+        self.variables[dst_variable_id] =
+            self.variables[dst_variable_id].wrapping_add(self.variables[src_variable_id]);
+        trace_op!(
+            self,
+            "add",
+            "var[{dst_variable_id:#04X}] += var[{src_variable_id:#04X}] -> {}",
+            self.variables[dst_variable_id]
+        );
         Ok(())
     }
 
@@ -180,6 +569,12 @@ impl Vm {
         let variable_id = bytecode.read_u8()? as usize;
         let value = bytecode.read_u16::<BigEndian>()? as i16;
         self.variables[variable_id] = self.variables[variable_id].wrapping_add(value);
+        trace_op!(
+            self,
+            "add_const",
+            "var[{variable_id:#04X}] += {value} -> {}",
+            self.variables[variable_id]
+        );
         Ok(())
     }
 
@@ -187,14 +582,19 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let offset: u16 = bytecode.read_u16::<BigEndian>()?;
 
+        if self.stack.len() >= self.max_call_depth {
+            return Err(VmError::StackOverflow);
+        }
         self.stack.push(bytecode.position());
-        bytecode.seek(SeekFrom::Start(offset as u64))?;
+        context.loaded_part.seek_bytecode(offset.into())?;
+        trace_op!(self, "call", "{offset:#06X}");
         Ok(())
     }
 
     pub fn op_ret(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let offset = self.stack.pop().ok_or(VmError::StackUnderflow)?;
         context.loaded_part.bytecode.seek(SeekFrom::Start(offset))?;
+        trace_op!(self, "ret", "{offset:#06X}");
         Ok(())
     }
 
@@ -202,13 +602,15 @@ impl Vm {
         let current_channel_id = self.running_channel_id;
         let execution_pc = context.loaded_part.bytecode.position().into();
         self.channels[current_channel_id].yield_control(execution_pc);
+        trace_op!(self, "yield_channel");
         Ok(())
     }
 
     pub fn op_jmp(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let bytecode = &mut context.loaded_part.bytecode;
         let offset = bytecode.read_u16::<BigEndian>()?;
-        bytecode.seek(SeekFrom::Start(offset as u64))?;
+        context.loaded_part.seek_bytecode(offset.into())?;
+        trace_op!(self, "jmp", "{offset:#06X}");
         Ok(())
     }
 
@@ -216,14 +618,32 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let channel_id = bytecode.read_u8()?;
         let offset = bytecode.read_u16::<BigEndian>()?;
-        self.channels[channel_id as usize].next_pc = Some(ProcessCounter::from(offset as u64));
+        let next_pc = ProcessCounter::from(offset as u64);
+        if let Some(offset) = next_pc.offset() {
+            context.loaded_part.check_bytecode_offset(offset)?;
+        }
+        self.channels
+            .get_mut(channel_id as usize)
+            .ok_or(VmError::InvalidChannel(channel_id))?
+            .next_pc = Some(next_pc);
+        trace_op!(self, "set_next_pc", "ch[{channel_id}] -> {offset:#06X}");
         Ok(())
     }
 
     pub fn op_jnz(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let bytecode = &mut context.loaded_part.bytecode;
         let variable_id = bytecode.read_u8()? as usize;
-        self.variables[variable_id] -= 1;
+        // Wraps like the original's 16-bit decrement: a counter starting at
+        // zero or below goes through i16::MIN to i16::MAX and down again, so
+        // the loop still ends, after at most 65536 iterations, and a long
+        // body is cut short by the watchdog instead of hanging.
+        self.variables[variable_id] = self.variables[variable_id].wrapping_sub(1);
+        trace_op!(
+            self,
+            "jnz",
+            "var[{variable_id:#04X}] -= 1 -> {}",
+            self.variables[variable_id]
+        );
         if self.variables[variable_id] != 0 {
             self.op_jmp(context)?;
         } else {
@@ -252,8 +672,13 @@ impl Vm {
             3 => b >= a,
             4 => a > b,
             5 => a >= b,
-            _ => false,
+            _ => return Err(VmError::UnexpectedComparison(comparison)),
         };
+        trace_op!(
+            self,
+            "cond_jmp",
+            "op={comparison} var[{var:#04X}]={b} operand={a} -> {expr}"
+        );
 
         if expr {
             self.op_jmp(context)?;
@@ -268,25 +693,34 @@ impl Vm {
         let palette_id = context.loaded_part.bytecode.read_u16::<BigEndian>()?;
         let palette_request = PaletteRequest::Change((palette_id >> 8) as u8);
         context.video.request_palette(palette_request);
+        trace_op!(self, "set_palette", "{}", palette_id >> 8);
         Ok(())
     }
 
     pub fn op_reset_threads(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let bytecode = &mut context.loaded_part.bytecode;
         let from = bytecode.read_u8()? as usize;
-        let to = bytecode.read_u8()? as usize;
+        let to = min(bytecode.read_u8()? as usize, NUM_CHANNELS - 1);
         let operation_id = bytecode.read_u8()?;
+        if from > to {
+            return Err(VmError::InvalidChannelRange(from, to));
+        }
         let operation: fn(&mut Channel) = match operation_id {
             0 => |channel| channel.state = State::Ready,
             1 => |channel| channel.state = State::Paused,
             _ => |channel| channel.next_pc = Some(ProcessCounter::Invalid),
         };
         self.channels[from..=to].iter_mut().for_each(operation);
+        if operation_id >= 2 {
+            context.audio.stop_owned_by(from..=to);
+        }
+        trace_op!(self, "reset_threads", "ch[{from}..={to}] op={operation_id}");
         Ok(())
     }
 
     pub fn op_select_video_page(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let page_id = PageId::from(context.loaded_part.bytecode.read_u8()?);
+        trace_op!(self, "select_page", "{page_id:?}");
         context.video.change_working_buffer(page_id);
         Ok(())
     }
@@ -295,6 +729,7 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let page_id = PageId::from(bytecode.read_u8()?);
         let color = bytecode.read_u8()?;
+        trace_op!(self, "fill_page", "{page_id:?} color={color}");
         context.video.fill_page(page_id, color);
         Ok(())
     }
@@ -303,6 +738,7 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let src_page_id = PageId::from(bytecode.read_u8()?);
         let dst_page_id = PageId::from(bytecode.read_u8()?);
+        trace_op!(self, "copy_page", "{src_page_id:?} -> {dst_page_id:?}");
         context.video.copy_page(
             src_page_id,
             dst_page_id,
@@ -312,23 +748,29 @@ impl Vm {
     }
 
     pub fn op_blit_frame_buffer(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
-        let elapsed = context.last_rendering.elapsed().as_millis();
-        let sleep = self.variables[VM_VARIABLE_PAUSE_SLICES] * 20 - elapsed as i16;
-        thread::sleep(Duration::from_millis(max(0, sleep) as u64));
-        context.last_rendering = Instant::now();
+        context
+            .frame_limiter
+            .wait_for_blit(self.variables[VM_VARIABLE_PAUSE_SLICES]);
 
+        // The original clears 0xF7 on every blit without ever reading it. Pause
+        // is handled by the host and does not go through a VM variable.
         self.variables[0xF7] = 0;
 
         let page_id = PageId::from(context.loaded_part.bytecode.read_u8()?);
+        trace_op!(self, "blit", "{page_id:?}");
 
         let video = &mut context.video;
         let palette = &mut context.loaded_part.palette;
         Ok(video.update_display(page_id, palette)?)
     }
 
-    pub fn op_kill_channel(&mut self, _: &mut ExecutionContext) -> Result<(), VmError> {
+    pub fn op_kill_channel(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let current_channel = self.running_channel_id;
         self.channels[current_channel].set_pc(ProcessCounter::Invalid);
+        context
+            .audio
+            .stop_owned_by(current_channel..=current_channel);
+        trace_op!(self, "kill_channel");
         Ok(())
     }
 
@@ -338,6 +780,11 @@ impl Vm {
         let x = bytecode.read_u8()? as u16;
         let y = bytecode.read_u8()? as u16;
         let color = bytecode.read_u8()?;
+        trace_op!(
+            self,
+            "draw_string",
+            "id={string_id:#05X} x={x} y={y} color={color}"
+        );
         context.video.draw_string(color, x, y, string_id);
         Ok(())
     }
@@ -346,7 +793,14 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let dst_variable_id = bytecode.read_u8()? as usize;
         let src_variable_id = bytecode.read_u8()? as usize;
-        self.variables[dst_variable_id] -= self.variables[src_variable_id];
+        self.variables[dst_variable_id] =
+            self.variables[dst_variable_id].wrapping_sub(self.variables[src_variable_id]);
+        trace_op!(
+            self,
+            "sub",
+            "var[{dst_variable_id:#04X}] -= var[{src_variable_id:#04X}] -> {}",
+            self.variables[dst_variable_id]
+        );
         Ok(())
     }
 
@@ -355,6 +809,12 @@ impl Vm {
         let variable_id = bytecode.read_u8()? as usize;
         let value = bytecode.read_u16::<BigEndian>()?;
         self.variables[variable_id] &= value as i16;
+        trace_op!(
+            self,
+            "and",
+            "var[{variable_id:#04X}] &= {value:#06X} -> {}",
+            self.variables[variable_id]
+        );
         Ok(())
     }
 
@@ -363,6 +823,12 @@ impl Vm {
         let variable_id = bytecode.read_u8()? as usize;
         let value = bytecode.read_u16::<BigEndian>()?;
         self.variables[variable_id] |= value as i16;
+        trace_op!(
+            self,
+            "or",
+            "var[{variable_id:#04X}] |= {value:#06X} -> {}",
+            self.variables[variable_id]
+        );
         Ok(())
     }
 
@@ -370,7 +836,17 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let variable_id = bytecode.read_u8()? as usize;
         let value = bytecode.read_u16::<BigEndian>()?;
-        self.variables[variable_id] <<= value as i16;
+        // Shifting a 16-bit value by 16 or more leaves nothing, as it did in
+        // the original once truncated back to 16 bits.
+        self.variables[variable_id] = self.variables[variable_id]
+            .checked_shl(value.into())
+            .unwrap_or(0);
+        trace_op!(
+            self,
+            "shl",
+            "var[{variable_id:#04X}] <<= {value:#06X} -> {}",
+            self.variables[variable_id]
+        );
         Ok(())
     }
 
@@ -378,7 +854,13 @@ impl Vm {
         let bytecode = &mut context.loaded_part.bytecode;
         let variable_id = bytecode.read_u8()? as usize;
         let value = bytecode.read_u16::<BigEndian>()?;
-        self.variables[variable_id] >>= value as i16;
+        self.variables[variable_id] >>= value.min(15);
+        trace_op!(
+            self,
+            "shr",
+            "var[{variable_id:#04X}] >>= {value:#06X} -> {}",
+            self.variables[variable_id]
+        );
         Ok(())
     }
 
@@ -388,11 +870,30 @@ impl Vm {
         let freq = bytecode.read_u8()?;
         let vol = bytecode.read_u8()?;
         let channel = bytecode.read_u8()?;
+        trace_op!(
+            self,
+            "play_sound",
+            "id={resource_id:#04X} freq={freq} ({} Hz) vol={vol} channel={channel}",
+            frequency_for(freq)
+        );
+        let request = if resource_id == 0 || vol == 0 {
+            SoundRequest::Stop
+        } else {
+            SoundRequest::Play {
+                sample: Sample::load(&mut context.resource, resource_id as usize)?,
+                frequency: frequency_for(freq),
+                volume: vol,
+            }
+        };
+        context
+            .audio
+            .play_sound(channel, request, self.running_channel_id);
         Ok(())
     }
 
     pub fn op_update_mem_list(&mut self, context: &mut ExecutionContext) -> Result<(), VmError> {
         let resource_id = context.loaded_part.bytecode.read_u16::<BigEndian>()?;
+        trace_op!(self, "update_mem_list", "{resource_id:#06X}");
         match resource_id {
             0 => context.loaded_asset = LoadedAsset::default(),
             1..NUM_MEM_ENTRIES => {
@@ -401,8 +902,12 @@ impl Vm {
                     .loaded_asset
                     .assets
                     .insert(resource_id as usize, asset);
+                context.loaded_asset.last_loaded = Some(resource_id as usize);
             }
-            _ => context.part_to_load = Some(GamePart::try_from_primitive(resource_id)?),
+            FIRST_PART_ID..=LAST_PART_ID => {
+                context.part_to_load = Some(GamePart::try_from_primitive(resource_id)?)
+            }
+            _ => return Err(VmError::InvalidResource(resource_id)),
         };
         Ok(())
     }
@@ -412,47 +917,34 @@ impl Vm {
         let resource_id = bytecode.read_u16::<BigEndian>()?;
         let delay = bytecode.read_u16::<BigEndian>()?;
         let offset = bytecode.read_u8()?;
+        trace_op!(
+            self,
+            "play_music",
+            "id={resource_id:#04X} delay={delay} offset={offset}"
+        );
+        let request = match (resource_id, delay) {
+            (0, 0) => MusicRequest::Stop,
+            (0, delay) => MusicRequest::SetDelay(delay),
+            (resource_id, delay) => MusicRequest::Play {
+                module: Module::load(&mut context.resource, resource_id as usize)?,
+                track: context.audio.replacement_track(resource_id as usize),
+                delay,
+                position: offset,
+            },
+        };
+        context.audio.play_music(request);
         Ok(())
     }
 
     fn draw_sprite(&mut self, opcode: u8, context: &mut ExecutionContext) -> Result<(), VmError> {
-        let bytecode = &mut context.loaded_part.bytecode;
-        let offset: u16 = bytecode.read_u16::<BigEndian>()? * 2;
-
-        let mut x: i16 = bytecode.read_u8()? as i16;
-        if opcode & 0x20 == 0 {
-            if opcode & 0x10 == 0 {
-                x = (x << 8) | bytecode.read_u8()? as i16;
-            } else {
-                x = self.variables[x as usize];
-            }
-        } else if opcode & 0x10 != 0 {
-            x += 0x100;
-        }
+        let sprite = SpriteOperands::read(opcode, &mut context.loaded_part.bytecode)?;
+        let offset = sprite.offset;
+        let x = sprite.x.resolve(&self.variables);
+        let y = sprite.y.resolve(&self.variables);
+        let zoom = sprite.zoom.resolve(&self.variables) as u16;
 
-        let mut y: i16 = bytecode.read_u8()? as i16;
-        if opcode & 8 == 0 {
-            if opcode & 4 == 0 {
-                y = (y << 8) | bytecode.read_u8()? as i16;
-            } else {
-                y = self.variables[y as usize];
-            }
-        }
-
-        let mut zoom: u16 = bytecode.read_u8()? as u16;
-        if opcode & 2 == 0 {
-            if opcode & 1 == 0 {
-                bytecode.seek(SeekFrom::Current(-1))?;
-                zoom = 0x40;
-            } else {
-                zoom = self.variables[zoom as usize] as u16;
-            }
-        } else if opcode & 1 != 0 {
-            bytecode.seek(SeekFrom::Current(-1))?;
-            zoom = 0x40;
-        }
-
-        if opcode & 3 != 3 {
+        trace_op!(self, "draw_sprite", "{offset:#06X} x={x} y={y} zoom={zoom}");
+        if !sprite.from_polygon {
             let cinematic = &mut context.loaded_part.cinematic;
             cinematic.seek(SeekFrom::Start(offset as u64))?;
             return Ok(context.video.read_and_draw_polygon(
@@ -463,14 +955,19 @@ impl Vm {
             )?);
         }
 
-        if let Some(ref mut polygon) = context.loaded_part.polygon {
-            polygon.seek(SeekFrom::Start(offset as u64))?;
-            Ok(context
-                .video
-                .read_and_draw_polygon(polygon, 0xFF, zoom, Point { x, y })?)
-        } else {
-            Err(VmError::MissingPolygonSegment)
-        }
+        // Parts without a polygon segment are not expected to use this variant,
+        // the original engine would read from whatever segment was left loaded.
+        let Some(ref mut polygon) = context.loaded_part.polygon else {
+            warn!(
+                "Channel {} draws sprite {offset:#06X} from a missing polygon segment, skipping",
+                self.running_channel_id
+            );
+            return Ok(());
+        };
+        polygon.seek(SeekFrom::Start(offset as u64))?;
+        Ok(context
+            .video
+            .read_and_draw_polygon(polygon, 0xFF, zoom, Point { x, y })?)
     }
 
     fn draw_background(
@@ -478,18 +975,12 @@ impl Vm {
         opcode: u8,
         context: &mut ExecutionContext,
     ) -> Result<(), VmError> {
-        let bytecode = &mut context.loaded_part.bytecode;
-        let offset = ((u16::from(opcode) << 8) | bytecode.read_u8()? as u16).wrapping_mul(2);
-        let mut x: i16 = bytecode.read_u8()? as i16;
-        let mut y: i16 = bytecode.read_u8()? as i16;
-        let h: i16 = y - 199;
-        if h > 0 {
-            y = 199;
-            x += h;
-        }
+        let BackgroundOperands { offset, x, y } =
+            BackgroundOperands::read(opcode, &mut context.loaded_part.bytecode)?;
 
         let color = 0xFF;
         let zoom = 0x40;
+        trace_op!(self, "draw_background", "{offset:#06X} x={x} y={y}");
         let cinematic = &mut context.loaded_part.cinematic;
         cinematic.seek(SeekFrom::Start(offset as u64))?;
         Ok(context
@@ -497,3 +988,197 @@ impl Vm {
             .read_and_draw_polygon(cinematic, color, zoom, Point { x, y })?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaded::LoadedPart;
+
+    /// Runs `bytecode` on channel 0 for one host frame.
+    fn run_frame(bytecode: &[u8]) -> (Vm, ExecutionContext) {
+        let mut context = ExecutionContext::headless(LoadedPart::from_bytecode(bytecode.to_vec()));
+        let mut vm = Vm::new(VmConfig::for_version(GameVersion::Dos, 0));
+        vm.init_part().unwrap();
+        vm.host_frame(&mut context).unwrap();
+        (vm, context)
+    }
+
+    /// Runs `bytecode` on channel 0 for one host frame and returns its error.
+    fn frame_error(bytecode: &[u8]) -> VmError {
+        let mut context = ExecutionContext::headless(LoadedPart::from_bytecode(bytecode.to_vec()));
+        let mut vm = Vm::new(VmConfig::for_version(GameVersion::Dos, 0));
+        vm.init_part().unwrap();
+        vm.host_frame(&mut context).unwrap_err()
+    }
+
+    #[test]
+    fn mov_const_sets_signed_value() {
+        let (vm, _) = run_frame(&[0x00, 0x10, 0xFF, 0xFE, 0x11]);
+        assert_eq!(vm.variables[0x10], -2);
+    }
+
+    #[test]
+    fn add_const_adds_signed_value() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x10, 0x00, 0x05,
+            0x03, 0x10, 0xFF, 0xFD,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x10], 2);
+    }
+
+    #[test]
+    fn cond_jmp_taken_skips_to_target() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x01, 0x00, 0x05,
+            // var[0x01] == 5 -> 0x000F
+            0x0A, 0x40, 0x01, 0x00, 0x05, 0x00, 0x0F,
+            0x00, 0x02, 0x00, 0x01,
+            0x06,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x02], 0);
+        assert_eq!(vm.channels[0].pc.offset(), Some(0x10));
+    }
+
+    #[test]
+    fn cond_jmp_not_taken_falls_through() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x01, 0x00, 0x05,
+            // var[0x01] > 7 -> 0x000E
+            0x0A, 0x02, 0x01, 0x07, 0x00, 0x0E,
+            0x00, 0x02, 0x00, 0x01,
+            0x06,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x02], 1);
+        assert_eq!(vm.channels[0].pc.offset(), Some(0x0F));
+    }
+
+    #[test]
+    fn cond_jmp_compares_against_variable() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x01, 0x00, 0x03,
+            0x00, 0x02, 0x00, 0x09,
+            // var[0x01] < var[0x02] -> 0x0013
+            0x0A, 0x84, 0x01, 0x02, 0x00, 0x13,
+            0x00, 0x03, 0x00, 0x01,
+            0x11,
+            0x06,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x03], 0);
+        assert_eq!(vm.channels[0].pc.offset(), Some(0x14));
+    }
+
+    #[test]
+    fn add_and_sub_wrap_around() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x01, 0x7F, 0xFF,
+            0x00, 0x02, 0x00, 0x01,
+            0x00, 0x03, 0x80, 0x00,
+            0x02, 0x01, 0x02,
+            0x13, 0x03, 0x02,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x01], i16::MIN);
+        assert_eq!(vm.variables[0x03], i16::MAX);
+    }
+
+    #[test]
+    fn shifts_of_16_or_more_do_not_panic() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x01, 0x00, 0x01,
+            0x00, 0x02, 0x80, 0x00,
+            0x00, 0x03, 0x40, 0x00,
+            0x16, 0x01, 0x00, 0x10,
+            0x17, 0x02, 0x00, 0x14,
+            0x16, 0x03, 0xFF, 0xFF,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x01], 0);
+        assert_eq!(vm.variables[0x02], -1);
+        assert_eq!(vm.variables[0x03], 0);
+    }
+
+    #[test]
+    fn set_next_pc_rejects_unknown_channel() {
+        let error = frame_error(&[0x08, 0x40, 0x00, 0x00, 0x11]);
+        assert!(matches!(error, VmError::InvalidChannel(0x40)));
+    }
+
+    #[test]
+    fn reset_threads_clamps_to_the_last_channel() {
+        let (vm, _) = run_frame(&[0x0C, 60, 200, 0x01, 0x11]);
+        assert!(vm.channels[59].state != State::Paused);
+        assert!(
+            vm.channels[60..]
+                .iter()
+                .all(|channel| channel.state == State::Paused)
+        );
+    }
+
+    #[test]
+    fn reset_threads_rejects_reversed_range() {
+        let error = frame_error(&[0x0C, 10, 5, 0x01, 0x11]);
+        assert!(matches!(error, VmError::InvalidChannelRange(10, 5)));
+    }
+
+    #[test]
+    fn jnz_from_negative_counter_wraps_down_to_zero() {
+        for (start, iterations) in [(-1i16, 65535u16), (i16::MIN, 32768)] {
+            let [hi, lo] = start.to_be_bytes();
+            #[rustfmt::skip]
+            let (vm, _) = run_frame(&[
+                0x00, 0x01, hi, lo,
+                0x03, 0x02, 0x00, 0x01,
+                // --var[0x01] != 0 -> 0x0004
+                0x09, 0x01, 0x00, 0x04,
+                0x11,
+            ]);
+            assert_eq!(vm.variables[0x01], 0, "{start}");
+            assert_eq!(vm.variables[0x02] as u16, iterations, "{start}");
+        }
+    }
+
+    #[test]
+    fn skipped_channel_is_reported_as_failed() {
+        let (vm, _) = run_frame(&[0x11]);
+        assert!(vm.is_halted() && !vm.has_failed_channels());
+
+        let mut context = ExecutionContext::headless(LoadedPart::from_bytecode(vec![0x30]));
+        let mut vm = Vm::new(VmConfig::for_version(GameVersion::Dos, 0));
+        vm.set_error_policy(ErrorPolicy::SkipChannel);
+        vm.init_part().unwrap();
+        vm.host_frame(&mut context).unwrap();
+        assert!(vm.is_halted() && vm.has_failed_channels());
+    }
+
+    #[test]
+    fn call_loop_overflows_the_stack() {
+        let error = frame_error(&[0x04, 0x00, 0x00]);
+        assert!(matches!(error, VmError::StackOverflow));
+    }
+
+    #[test]
+    fn jnz_loops_until_counter_reaches_zero() {
+        #[rustfmt::skip]
+        let (vm, _) = run_frame(&[
+            0x00, 0x01, 0x00, 0x03,
+            0x03, 0x02, 0x00, 0x01,
+            // --var[0x01] != 0 -> 0x0004
+            0x09, 0x01, 0x00, 0x04,
+            0x06,
+            0x11,
+        ]);
+        assert_eq!(vm.variables[0x01], 0);
+        assert_eq!(vm.variables[0x02], 3);
+        assert_eq!(vm.channels[0].pc.offset(), Some(0x0D));
+    }
+}